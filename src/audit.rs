@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use crate::challenge::{Author, Challenge, Submission};
+use crate::engine::Change;
+
+/// A moderator action, along with the data it removed.
+#[derive(Serialize, Deserialize)]
+pub enum Action {
+    Close {
+        challenge: Challenge,
+    },
+    Refuse {
+        challenge: String,
+        submission: Submission,
+    },
+    Approve {
+        challenge: String,
+    },
+    Reject {
+        challenge: Challenge,
+        reason: String,
+    },
+    Config {
+        key: String,
+        value: String,
+    },
+    /// The challenge as it was before the edit.
+    Edit {
+        challenge: Challenge,
+    },
+    Reopen {
+        challenge: String,
+    },
+    Revalidate {
+        challenge: String,
+        changes: Vec<Change>,
+    },
+}
+
+impl Action {
+    /// The id of the challenge this action is about, if any.
+    pub fn challenge_id(&self) -> Option<&str> {
+        match self {
+            Action::Close { challenge }
+            | Action::Reject { challenge, .. }
+            | Action::Edit { challenge } => Some(&challenge.id),
+            Action::Refuse { challenge, .. }
+            | Action::Approve { challenge }
+            | Action::Reopen { challenge }
+            | Action::Revalidate { challenge, .. } => Some(challenge),
+            Action::Config { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Close { challenge } => write!(
+                f,
+                "closed `{}` ({} submissions)",
+                challenge.id,
+                challenge.scores.len()
+            ),
+            Action::Refuse {
+                challenge,
+                submission,
+            } => write!(
+                f,
+                "refused the submission of {} on `{}` : `{}` ({} keys)",
+                submission.author, challenge, submission.keys, submission.score
+            ),
+            Action::Approve { challenge } => write!(f, "approved `{}`", challenge),
+            Action::Reject { challenge, reason } => {
+                write!(f, "rejected `{}` : {}", challenge.id, reason)
+            }
+            Action::Config { key, value } => write!(f, "set {} to {}", key, value),
+            Action::Edit { challenge } => write!(f, "edited `{}`", challenge.id),
+            Action::Reopen { challenge } => write!(f, "reopened `{}`", challenge),
+            Action::Revalidate { challenge, changes } => write!(
+                f,
+                "evaluated the submissions of `{}` again : {}",
+                challenge,
+                changes
+                    .iter()
+                    .map(|change| change.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// An entry of the audit log.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: i64,
+    pub moderator: Author,
+    pub action: Action,
+}
+
+impl Entry {
+    /// The audit log holds one entry per line.
+    pub const FILE: &'static str = "audit.ron";
+
+    pub fn new(timestamp: i64, moderator: Author, action: Action) -> Self {
+        Entry {
+            timestamp,
+            moderator,
+            action,
+        }
+    }
+
+    /// Appends the entry to the audit log.
+    pub fn record(&self) -> ron::Result<()> {
+        let mut line = ron::ser::to_string(self)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::FILE)?;
+        file.write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Reads the whole audit log, oldest entries first.
+    pub fn all() -> Vec<Self> {
+        match File::open(Self::FILE) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .filter_map(|line| ron::de::from_str(&line.ok()?).ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
@@ -1,84 +1,655 @@
 use serde::{Deserialize, Serialize};
 
-use std::fs::File;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::iter;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::str::Lines;
 
 use glob::glob;
+use sha1::{Digest, Sha1};
 
-pub trait FromLines: Sized {
-    type Error;
+use crate::constraint::Constraint;
+use crate::parser::Draft;
+use crate::postcondition::Postcondition;
+use crate::vim;
 
-    fn from_lines(lines: &mut Lines) -> Result<Self, Self::Error>;
+/// Everything that can go wrong when reading a challenge, from a message or from disk.
+#[derive(Debug)]
+pub enum ChallengeError {
+    /// The message contains nothing after the command.
+    Empty,
+    /// The challenge does not start with a `# Title`.
+    MissingTitle { line: usize },
+    /// The code block opened at `line` is never closed.
+    UnterminatedFence { line: usize },
+    /// A section appears twice.
+    DuplicateSection { line: usize, section: &'static str },
+    /// Something other than a code block is in the Input or Output section.
+    UnexpectedContent { line: usize, section: &'static str },
+    /// A line of the Settings section is not a known `key = value` setting.
+    InvalidSetting { line: usize, setting: String },
+    /// The value of the setting `key` is not valid.
+    InvalidValue {
+        line: usize,
+        key: String,
+        reason: String,
+    },
+    /// The input and the output are the same.
+    Trivial,
+    /// The `what` code block has no content.
+    EmptyBlock { what: &'static str },
+    /// `what` is longer than `limit`.
+    TooLarge { what: &'static str, limit: String },
+    /// The language of a code block is not known.
+    UnknownLang(String),
+    /// The challenge file is not a valid TOML challenge.
+    Toml(toml::de::Error),
+    /// An attached file is not valid UTF-8.
+    NotUtf8,
+    /// An attached file is not part of a challenge.
+    UnexpectedFile(String),
+    /// The error happened while reading the attached `file`.
+    InFile {
+        file: String,
+        error: Box<ChallengeError>,
+    },
+    /// The message ended before the `what` code block.
+    MissingBlock { what: &'static str },
+    /// Several buffers have the same name.
+    DuplicateBuffer(String),
+    /// The section of the buffer `name` does not contain both its input and its output.
+    IncompleteBuffer { name: String },
+    /// The challenge requires Vim, but uses features only evaluated in nvim.
+    NeovimOnly,
+    /// The challenge requires Vim, but Vim can't be started by the bot.
+    VimUnavailable,
+    /// The id contains characters that can't appear in an id.
+    InvalidId(String),
+    /// No challenge is designated by the id.
+    NotFound(ChallengeId),
+    /// Several challenges are designated by the id.
+    Ambiguous(ChallengeId, Vec<String>),
+    /// The challenge file can't be read.
+    Io { id: String, source: std::io::Error },
+    /// The challenge file is not a valid challenge.
+    Deserialize { id: String, source: ron::Error },
+    /// The challenge file can't be written.
+    Serialize { id: String, source: ron::Error },
+    /// The name is already the id or the slug of another challenge.
+    NameTaken(String),
+    /// The challenges can't be locked before being written.
+    Lock(std::io::Error),
 }
 
-impl FromLines for String {
-    type Error = String;
+impl fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChallengeError::Empty => write!(f, "the challenge is empty"),
+            ChallengeError::MissingTitle { line } => write!(
+                f,
+                "line {} : expected a title, like `# Challenge Title`",
+                line
+            ),
+            ChallengeError::UnterminatedFence { line } => write!(
+                f,
+                "line {} : this code block is never closed by a matching fence",
+                line
+            ),
+            ChallengeError::DuplicateSection { line, section } => {
+                write!(f, "line {} : the {} section appears twice", line, section)
+            }
+            ChallengeError::UnexpectedContent { line, section } => write!(
+                f,
+                "line {} : the {} section should only contain one code block",
+                line, section
+            ),
+            ChallengeError::InvalidSetting { line, setting } => write!(
+                f,
+                "line {} : `{}` is not a valid setting, expected `key = value` with one of : {}",
+                line,
+                setting,
+                Draft::SETTINGS.join(", ")
+            ),
+            ChallengeError::InvalidValue { line, key, reason } => {
+                write!(f, "line {} : invalid `{}` setting, {}", line, key, reason)
+            }
+            ChallengeError::MissingBlock { what } => {
+                write!(f, "the message ended before the {} code block", what)
+            }
+            ChallengeError::DuplicateBuffer(name) => {
+                write!(f, "several buffers are named `{}`", name)
+            }
+            ChallengeError::IncompleteBuffer { name } => write!(
+                f,
+                "the `{}` buffer should contain an input code block, then an output code block",
+                name
+            ),
+            ChallengeError::NeovimOnly => write!(
+                f,
+                "checkers, generators, post-conditions, buffers, file names, runtime and rules on \
+                modes or commands only work in Neovim, submissions can't be required to work in Vim"
+            ),
+            ChallengeError::VimUnavailable => write!(
+                f,
+                "Vim is not available, submissions can't be required to work in Vim"
+            ),
+            ChallengeError::Trivial => write!(f, "the input and the output are the same"),
+            ChallengeError::EmptyBlock { what } => write!(f, "the {} is empty", what),
+            ChallengeError::TooLarge { what, limit } => {
+                write!(f, "the {} is too long, the limit is {}", what, limit)
+            }
+            ChallengeError::UnknownLang(lang) => write!(
+                f,
+                "`{}` is not a known language, use one of : {}",
+                lang,
+                Challenge::LANGS.join(", ")
+            ),
+            ChallengeError::Toml(err) => write!(f, "{}", err),
+            ChallengeError::NotUtf8 => write!(f, "the file is not valid UTF-8 text"),
+            ChallengeError::UnexpectedFile(file) => write!(
+                f,
+                "`{}` was not expected, attach either a `.md` or `.toml` challenge, \
+                or `input` and `output` files",
+                file
+            ),
+            ChallengeError::InFile { file, error } => write!(f, "in `{}`, {}", file, error),
+            ChallengeError::InvalidId(id) => write!(f, "`{}` is not a valid challenge id", id),
+            ChallengeError::NotFound(id) => write!(f, "there is no `{}` challenge", id),
+            ChallengeError::Ambiguous(id, matches) => write!(
+                f,
+                "`{}` matches several challenges : `{}`",
+                id,
+                matches.join("`, `")
+            ),
+            ChallengeError::Io { id, source } => {
+                write!(f, "the `{}` challenge file can't be read ({})", id, source)
+            }
+            ChallengeError::Deserialize { id, source } => {
+                write!(f, "the `{}` challenge file is corrupt ({})", id, source)
+            }
+            ChallengeError::Serialize { id, source } => {
+                write!(
+                    f,
+                    "the `{}` challenge file can't be written ({})",
+                    id, source
+                )
+            }
+            ChallengeError::NameTaken(name) => write!(f, "the `{}` name is already taken", name),
+            ChallengeError::Lock(err) => write!(f, "the challenges can't be locked ({})", err),
+        }
+    }
+}
 
-    fn from_lines(lines: &mut Lines) -> Result<Self, Self::Error> {
-        let mut content = String::new();
-        let mut is_filling = false;
+impl std::error::Error for ChallengeError {}
 
-        for line in lines {
-            if line.starts_with("```") {
-                return Err(String::from("``` lines are prohibited in paragraphs."))
-            } else if line.is_empty() {
-                if is_filling {
-                    // Finished to extract paragraph
-                    return Ok(content);
-                } else {
-                    is_filling = true;
-                }
-            } else if is_filling {
-                content.push_str(" ");
-                content.push_str(line);
-            }
+/// How lines are separated, as nvim's `'fileformat'`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FileFormat {
+    #[default]
+    Unix,
+    Dos,
+}
+
+impl FileFormat {
+    /// The value of nvim's `'fileformat'` option.
+    pub fn option(self) -> &'static str {
+        match self {
+            FileFormat::Unix => "unix",
+            FileFormat::Dos => "dos",
+        }
+    }
+
+    pub fn from_option(option: &str) -> Option<Self> {
+        match option {
+            "unix" => Some(FileFormat::Unix),
+            "dos" => Some(FileFormat::Dos),
+            _ => None,
+        }
+    }
+
+    pub fn separator(self) -> &'static str {
+        match self {
+            FileFormat::Unix => "\n",
+            FileFormat::Dos => "\r\n",
+        }
+    }
+}
+
+/// A difference between an output and the expected one that is tolerated.
+///
+/// The comparison is strict when none is tolerated.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    /// Whitespace at the end of lines is ignored.
+    TrailingWhitespace,
+    /// Blank lines at the end of the text are ignored, along with the final line separator.
+    TrailingBlankLines,
+    /// Letters are compared regardless of their case.
+    IgnoreCase,
+    /// Runs of whitespace are taken as a single space, and whitespace around lines is ignored.
+    CollapseWhitespace,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 4] = [
+        Comparison::TrailingWhitespace,
+        Comparison::TrailingBlankLines,
+        Comparison::IgnoreCase,
+        Comparison::CollapseWhitespace,
+    ];
+
+    /// The name used in the `compare` setting.
+    pub fn name(self) -> &'static str {
+        match self {
+            Comparison::TrailingWhitespace => "trailing-whitespace",
+            Comparison::TrailingBlankLines => "trailing-blank-lines",
+            Comparison::IgnoreCase => "ignore-case",
+            Comparison::CollapseWhitespace => "collapse-whitespace",
+        }
+    }
+
+    /// Reads the value of the `compare` setting, `strict` meaning that nothing is tolerated.
+    pub fn from_setting(value: &str) -> Result<Vec<Self>, String> {
+        if value.trim().eq_ignore_ascii_case("strict") {
+            return Ok(Vec::new());
+        }
+
+        let comparison = value
+            .split_whitespace()
+            .map(|name| {
+                Self::ALL
+                    .iter()
+                    .copied()
+                    .find(|comparison| comparison.name().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        format!(
+                            "`{}` is not a comparison, expected `strict` or some of : {}",
+                            name,
+                            Self::ALL
+                                .iter()
+                                .map(|comparison| comparison.name())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if comparison.is_empty() {
+            Err(String::from("expected `strict` or a space separated list"))
+        } else {
+            Ok(comparison)
+        }
+    }
+
+    /// The value of the `compare` setting, as read by `from_setting`.
+    pub fn to_setting(comparison: &[Self]) -> String {
+        if comparison.is_empty() {
+            return String::from("strict");
+        }
+
+        comparison
+            .iter()
+            .map(|comparison| comparison.name())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Comparison::TrailingWhitespace => write!(f, "ignoring trailing whitespace"),
+            Comparison::TrailingBlankLines => write!(f, "ignoring trailing blank lines"),
+            Comparison::IgnoreCase => write!(f, "ignoring case"),
+            Comparison::CollapseWhitespace => write!(f, "collapsing runs of whitespace"),
+        }
+    }
+}
+
+/// A buffer besides the one holding the input, with its own expected output.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Buffer {
+    pub name: String,
+    pub input: TextBlock,
+    pub output: TextBlock,
+}
+
+/// How the buffers of a challenge are shown when the keys start.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Layout {
+    /// Only the input is shown, the other buffers are loaded but hidden.
+    #[default]
+    Hidden,
+    /// Each buffer is shown in a window, above the input.
+    Split,
+    /// Each buffer is shown in a window, left of the input.
+    Vsplit,
+    /// Each buffer is shown in a tab page, after the one of the input.
+    Tabs,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [Layout::Hidden, Layout::Split, Layout::Vsplit, Layout::Tabs];
+
+    /// The name used in the `layout` setting.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Hidden => "hidden",
+            Layout::Split => "split",
+            Layout::Vsplit => "vsplit",
+            Layout::Tabs => "tabs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|layout| layout.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The ex command opening a window for another buffer, `None` if it stays hidden.
+    pub fn command(self) -> Option<&'static str> {
+        match self {
+            Layout::Hidden => None,
+            Layout::Split => Some("split"),
+            Layout::Vsplit => Some("vsplit"),
+            Layout::Tabs => Some("$tab split"),
+        }
+    }
+}
+
+/// Lua code generating more inputs along with their outputs, that submissions have to solve too.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Generator {
+    pub code: String,
+    /// How many inputs are generated.
+    pub cases: usize,
+    /// Seeds the random numbers, so that the same inputs are generated each time.
+    pub seed: u32,
+}
+
+impl Generator {
+    pub const DEFAULT_CASES: usize = 5;
+    pub const MAX_CASES: usize = 20;
+}
+
+/// Parts of nvim's bundled runtime that can be loaded for the filetype of the buffers.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Runtime {
+    /// Filetype plugins, setting options like `'comments'` or `'formatoptions'`.
+    Ftplugin,
+    /// Indent scripts, used by `=` and when inserting lines.
+    Indent,
+    Syntax,
+}
+
+impl Runtime {
+    pub const ALL: [Runtime; 3] = [Runtime::Ftplugin, Runtime::Indent, Runtime::Syntax];
+
+    /// The name used in the `runtime` setting.
+    pub fn name(self) -> &'static str {
+        match self {
+            Runtime::Ftplugin => "ftplugin",
+            Runtime::Indent => "indent",
+            Runtime::Syntax => "syntax",
+        }
+    }
+
+    /// The ex command enabling this part of the runtime.
+    pub fn command(self) -> &'static str {
+        match self {
+            Runtime::Ftplugin => "filetype plugin on",
+            Runtime::Indent => "filetype indent on",
+            Runtime::Syntax => "syntax enable",
+        }
+    }
+
+    /// Reads the value of the `runtime` setting.
+    pub fn from_setting(value: &str) -> Result<Vec<Self>, String> {
+        let runtime = value
+            .split_whitespace()
+            .map(|name| {
+                Self::ALL
+                    .iter()
+                    .copied()
+                    .find(|runtime| runtime.name().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        format!(
+                            "`{}` is not a part of the runtime, expected some of : {}",
+                            name,
+                            Self::ALL
+                                .iter()
+                                .map(|runtime| runtime.name())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if runtime.is_empty() {
+            Err(String::from("expected a space separated list"))
+        } else {
+            Ok(runtime)
+        }
+    }
+
+    /// The value of the `runtime` setting, as read by `from_setting`.
+    pub fn to_setting(runtime: &[Self]) -> String {
+        runtime
+            .iter()
+            .map(|runtime| runtime.name())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The editors keys can be run in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Editor {
+    Neovim,
+    /// Classic Vim, whose defaults and commands differ from nvim's, like `Y` or `&`.
+    Vim,
+    Both,
+}
+
+impl Editor {
+    pub const ALL: [Editor; 3] = [Editor::Neovim, Editor::Vim, Editor::Both];
+
+    /// The name used in the `editor` setting.
+    pub fn name(self) -> &'static str {
+        match self {
+            Editor::Neovim => "neovim",
+            Editor::Vim => "vim",
+            Editor::Both => "both",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|editor| editor.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The editors keys work in, given whether they work in each one.
+    pub fn from_results(neovim: bool, vim: bool) -> Option<Self> {
+        match (neovim, vim) {
+            (true, true) => Some(Editor::Both),
+            (true, false) => Some(Editor::Neovim),
+            (false, true) => Some(Editor::Vim),
+            (false, false) => None,
         }
+    }
+
+    /// Whether keys working in these editors meet the `required` ones.
+    pub fn satisfies(self, required: Editor) -> bool {
+        self == Editor::Both || self == required
+    }
+}
 
-        Ok(content)
+impl fmt::Display for Editor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Editor::Neovim => write!(f, "Neovim"),
+            Editor::Vim => write!(f, "Vim"),
+            Editor::Both => write!(f, "both Vim and Neovim"),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+fn default_eol() -> bool {
+    true
+}
+
+/// The content of a buffer.
+///
+/// As in nvim, the text is stored as lines, along with the line separator and whether the last
+/// line ends with a separator.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TextBlock {
     pub lang: Option<String>,
     pub content: Vec<String>,
+    #[serde(default)]
+    pub fileformat: FileFormat,
+    /// Whether the last line ends with a line separator, as nvim's `'eol'`.
+    #[serde(default = "default_eol")]
+    pub eol: bool,
 }
 
-impl FromLines for TextBlock {
-    type Error = String;
-    fn from_lines(lines: &mut Lines) -> Result<Self, Self::Error> {
-        let mut lang: Option<String> = None;
-        let mut content: Vec<String> = Vec::new();
-        let mut is_filling = false;
+impl TextBlock {
+    /// A block of unix lines, each ending with a newline.
+    pub fn new(lang: Option<String>, content: Vec<String>) -> Self {
+        Self {
+            lang,
+            content,
+            fileformat: FileFormat::Unix,
+            eol: true,
+        }
+    }
 
-        for line in lines {
-            if line.starts_with("```") {
-                if is_filling {
-                    return Ok(Self::new(lang, content));
-                } else {
+    /// A block holding `content`, with the language, the line endings and the final newline of
+    /// this one.
+    pub fn with_content(&self, content: Vec<String>) -> Self {
+        Self {
+            lang: self.lang.clone(),
+            content,
+            fileformat: self.fileformat,
+            eol: self.eol,
+        }
+    }
 
-                    // Starting to read the block, we need to extract the lang too
-                    let line = line.strip_prefix("```").unwrap();
-                    if !line.is_empty() {
-                        lang = Some(line.to_owned());
-                    }
+    /// Splits `text` in lines the way nvim does when reading a file.
+    ///
+    /// The text is in the dos format only when every line ends with `\r\n`, otherwise the
+    /// carriage returns are kept in the lines.
+    pub fn from_text(lang: Option<String>, text: &str) -> Self {
+        if text.is_empty() {
+            return Self::new(lang, Vec::new());
+        }
 
-                    is_filling = true;
-                }
-            } else if is_filling {
-                content.push(line.to_owned());
+        let eol = text.ends_with('\n');
+        let body = if eol { &text[..text.len() - 1] } else { text };
+
+        let mut content: Vec<String> = body.split('\n').map(String::from).collect();
+
+        let is_dos = eol && content.iter().all(|line| line.ends_with('\r'));
+        if is_dos {
+            for line in content.iter_mut() {
+                line.pop();
             }
         }
 
-        Err(String::from("Failed to parse TextBlock, reached EOF."))
+        Self {
+            lang,
+            content,
+            fileformat: if is_dos {
+                FileFormat::Dos
+            } else {
+                FileFormat::Unix
+            },
+            eol,
+        }
     }
-}
 
-impl TextBlock {
-    pub fn new(lang: Option<String>, content: Vec<String>) -> Self {
-        Self { lang, content }
+    /// The nvim filetype matching the language of the block.
+    pub fn filetype(&self) -> Option<&str> {
+        let lang = self.lang.as_deref()?;
+
+        Some(match lang {
+            "bash" => "sh",
+            "js" => "javascript",
+            "md" => "markdown",
+            "plaintext" | "txt" => "text",
+            "py" => "python",
+            "rb" => "ruby",
+            "rs" => "rust",
+            "ts" => "typescript",
+            "yml" => "yaml",
+            other => other,
+        })
+    }
+
+    /// The exact text of the block, as nvim would write it.
+    pub fn to_text(&self) -> String {
+        if self.content.is_empty() {
+            return String::new();
+        }
+
+        let separator = self.fileformat.separator();
+
+        let mut text = self.content.join(separator);
+        if self.eol {
+            text.push_str(separator);
+        }
+
+        text
+    }
+
+    /// Whether both blocks hold the same text, regardless of their language.
+    pub fn same_text(&self, other: &Self) -> bool {
+        self.matches(other, &[])
+    }
+
+    /// Whether both blocks hold the same text, tolerating the differences in `comparison`.
+    pub fn matches(&self, other: &Self, comparison: &[Comparison]) -> bool {
+        let tolerates = |difference| comparison.contains(&difference);
+
+        let lines = |block: &Self| -> Vec<String> {
+            // An empty buffer still has one empty line in nvim
+            let mut lines = if block.content.is_empty() {
+                vec![String::new()]
+            } else {
+                block.content.clone()
+            };
+
+            for line in lines.iter_mut() {
+                if tolerates(Comparison::CollapseWhitespace) {
+                    *line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+                } else if tolerates(Comparison::TrailingWhitespace) {
+                    line.truncate(line.trim_end().len());
+                }
+
+                if tolerates(Comparison::IgnoreCase) {
+                    *line = line.to_lowercase();
+                }
+            }
+
+            if tolerates(Comparison::TrailingBlankLines) {
+                while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                    lines.pop();
+                }
+            }
+
+            lines
+        };
+
+        lines(self) == lines(other)
+            && self.fileformat == other.fileformat
+            && (self.eol == other.eol || tolerates(Comparison::TrailingBlankLines))
     }
 
     pub fn as_markdown(&self) -> String {
@@ -91,6 +662,14 @@ impl TextBlock {
         block.push_str(&self.content.join("\n"));
         block.push_str("\n```");
 
+        // Those can't be seen in the code block
+        if self.fileformat != FileFormat::Unix {
+            block.push_str(&format!("\n_{} line endings_", self.fileformat.option()));
+        }
+        if !self.eol {
+            block.push_str("\n_no newline at end of file_");
+        }
+
         block
     }
 
@@ -103,14 +682,44 @@ impl TextBlock {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Submission {
     pub author: String,
     pub score: usize,
     pub keys: String,
+    /// The editors the keys work in, unknown for the submissions made before Vim was supported.
+    #[serde(default)]
+    pub works_in: Option<Editor>,
+}
+
+/// A discord user, as stored in challenges and in the audit log.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Author {
+    /// Discord user id, used to notify the author.
+    pub id: u64,
+    pub name: String,
+}
+
+/// Where a challenge stands in the moderation workflow.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Status {
+    /// Registered, waiting for a moderator to review it.
+    Pending,
+    /// Approved and open to submissions.
+    ///
+    /// The default, since challenges stored before the moderation queue were published instantly.
+    #[default]
+    Open,
+    /// Closed, kept as an archive so that it can be reopened.
+    Closed,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Exclusive access to the stored challenges, released when dropped, see `Challenge::lock`.
+pub struct StoreLock {
+    _file: File,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Challenge {
     pub id: String,
     pub title: String,
@@ -119,10 +728,58 @@ pub struct Challenge {
     pub input: TextBlock,
     pub output: TextBlock,
     pub scores: Vec<Submission>,
+    #[serde(default)]
+    pub author: Option<Author>,
+    #[serde(default)]
+    pub status: Status,
+    /// Human-friendly alias of the id, chosen by the author.
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub hints: Vec<String>,
+    /// Id of the challenge this one is a variant of.
+    #[serde(default)]
+    pub forked_from: Option<String>,
+    #[serde(default)]
+    pub constraints: Vec<Constraint>,
+    /// Lua code deciding whether an output is valid, hidden from players.
+    #[serde(default)]
+    pub checker: Option<String>,
+    /// Differences tolerated between the output and the expected one.
+    #[serde(default)]
+    pub comparison: Vec<Comparison>,
+    /// Requirements on the state of the editor once the keys ran.
+    #[serde(default)]
+    pub postconditions: Vec<Postcondition>,
+    /// Name of the buffer holding the input, needed when other buffers refer to it.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Buffers besides the input, all their outputs have to match.
+    #[serde(default)]
+    pub buffers: Vec<Buffer>,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Parts of the runtime loaded for the filetype of the buffers, none by default.
+    #[serde(default)]
+    pub runtime: Vec<Runtime>,
+    /// Filetype of the input buffer, instead of the one given by its language.
+    #[serde(default)]
+    pub filetype: Option<String>,
+    #[serde(default)]
+    pub generator: Option<Generator>,
+    /// The score the author expects a good solution to reach.
+    #[serde(default)]
+    pub par: Option<usize>,
+    /// The editors submissions have to work in, either one if there is none.
+    #[serde(default)]
+    pub editor: Option<Editor>,
 }
 
 impl Challenge {
     pub const DIR: &'static str = "challenges";
+    /// The file locked while challenges are written, outside of `DIR` since it is not one.
+    pub const LOCK: &'static str = "challenges.lock";
+
     pub fn new(
         title: String,
         description: String,
@@ -130,6 +787,7 @@ impl Challenge {
         output: TextBlock,
         id: String,
         timestamp: i64,
+        author: Author,
     ) -> Self {
         Challenge {
             title,
@@ -139,14 +797,312 @@ impl Challenge {
             input,
             output,
             scores: Vec::new(),
+            author: Some(author),
+            status: Status::Pending,
+            slug: None,
+            hints: Vec::new(),
+            forked_from: None,
+            constraints: Vec::new(),
+            checker: None,
+            comparison: Vec::new(),
+            postconditions: Vec::new(),
+            file: None,
+            buffers: Vec::new(),
+            layout: Layout::Hidden,
+            runtime: Vec::new(),
+            filetype: None,
+            generator: None,
+            par: None,
+            editor: None,
+        }
+    }
+
+    /// The name shown to users, the slug if there is one.
+    pub fn name(&self) -> &str {
+        self.slug.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Languages accepted for code blocks.
+    pub const LANGS: &'static [&'static str] = &[
+        "bash",
+        "c",
+        "cpp",
+        "cs",
+        "css",
+        "csv",
+        "diff",
+        "go",
+        "haskell",
+        "html",
+        "ini",
+        "java",
+        "js",
+        "json",
+        "lua",
+        "markdown",
+        "md",
+        "ocaml",
+        "php",
+        "plaintext",
+        "py",
+        "python",
+        "rb",
+        "ruby",
+        "rs",
+        "rust",
+        "sh",
+        "sql",
+        "tex",
+        "text",
+        "toml",
+        "ts",
+        "txt",
+        "vim",
+        "xml",
+        "yaml",
+        "yml",
+    ];
+    pub const MAX_TITLE: usize = 100;
+    pub const MAX_DESCRIPTION: usize = 1000;
+    pub const MAX_LINES: usize = 200;
+    pub const MAX_BYTES: usize = 10_000;
+    pub const MAX_BUFFERS: usize = 4;
+    pub const MAX_HINTS: usize = 5;
+    pub const MAX_HINT: usize = 200;
+
+    /// Lists the problems preventing the challenge from being published.
+    pub fn validate(&self) -> Vec<ChallengeError> {
+        let mut problems = Vec::new();
+
+        if self.title.chars().count() > Self::MAX_TITLE {
+            problems.push(ChallengeError::TooLarge {
+                what: "title",
+                limit: format!("{} characters", Self::MAX_TITLE),
+            });
+        }
+
+        if self.description.chars().count() > Self::MAX_DESCRIPTION {
+            problems.push(ChallengeError::TooLarge {
+                what: "description",
+                limit: format!("{} characters", Self::MAX_DESCRIPTION),
+            });
+        }
+
+        if self.hints.len() > Self::MAX_HINTS {
+            problems.push(ChallengeError::TooLarge {
+                what: "list of hints",
+                limit: format!("{} hints", Self::MAX_HINTS),
+            });
+        }
+
+        if self
+            .hints
+            .iter()
+            .any(|hint| hint.chars().count() > Self::MAX_HINT)
+        {
+            problems.push(ChallengeError::TooLarge {
+                what: "hint",
+                limit: format!("{} characters", Self::MAX_HINT),
+            });
+        }
+
+        // An empty output is fine, deleting everything is a valid challenge
+        if self.input.to_text().trim().is_empty() {
+            problems.push(ChallengeError::EmptyBlock { what: "input" });
+        }
+
+        if self.buffers.len() > Self::MAX_BUFFERS {
+            problems.push(ChallengeError::TooLarge {
+                what: "list of buffers",
+                limit: format!("{} buffers besides the input", Self::MAX_BUFFERS),
+            });
+        }
+
+        let mut names: Vec<&String> = self.file.iter().collect();
+        for buffer in &self.buffers {
+            if names.contains(&&buffer.name) {
+                problems.push(ChallengeError::DuplicateBuffer(buffer.name.clone()));
+            }
+            names.push(&buffer.name);
+        }
+
+        let blocks = [("input", &self.input), ("output", &self.output)]
+            .iter()
+            .copied()
+            .chain(self.buffers.iter().flat_map(|buffer| {
+                vec![
+                    ("input of a buffer", &buffer.input),
+                    ("output of a buffer", &buffer.output),
+                ]
+            }))
+            .collect::<Vec<_>>();
+
+        for (what, block) in blocks {
+            if block.len() > Self::MAX_LINES {
+                problems.push(ChallengeError::TooLarge {
+                    what,
+                    limit: format!("{} lines", Self::MAX_LINES),
+                });
+            } else if block.to_text().len() > Self::MAX_BYTES {
+                problems.push(ChallengeError::TooLarge {
+                    what,
+                    limit: format!("{} bytes", Self::MAX_BYTES),
+                });
+            }
+
+            if let Some(lang) = &block.lang {
+                if !Self::LANGS.contains(&lang.to_ascii_lowercase().as_str()) {
+                    problems.push(ChallengeError::UnknownLang(lang.to_owned()));
+                }
+            }
+        }
+
+        if let Some(checker) = &self.checker {
+            if checker.len() > Self::MAX_BYTES {
+                problems.push(ChallengeError::TooLarge {
+                    what: "checker",
+                    limit: format!("{} bytes", Self::MAX_BYTES),
+                });
+            }
+        }
+
+        if let Some(generator) = &self.generator {
+            if generator.code.len() > Self::MAX_BYTES {
+                problems.push(ChallengeError::TooLarge {
+                    what: "generator",
+                    limit: format!("{} bytes", Self::MAX_BYTES),
+                });
+            }
         }
+
+        if matches!(self.editor, Some(Editor::Vim) | Some(Editor::Both)) && !vim::supports(self) {
+            problems.push(ChallengeError::NeovimOnly);
+        }
+
+        if matches!(self.editor, Some(Editor::Vim) | Some(Editor::Both)) && !vim::is_available() {
+            problems.push(ChallengeError::VimUnavailable);
+        }
+
+        let unchanged = |input: &TextBlock, output| input.matches(output, &self.comparison);
+
+        if unchanged(&self.input, &self.output)
+            && self
+                .buffers
+                .iter()
+                .all(|buffer| unchanged(&buffer.input, &buffer.output))
+        {
+            problems.push(ChallengeError::Trivial);
+        }
+
+        problems
+    }
+
+    /// The score of the naive solution, deleting everything then typing the outputs.
+    pub fn baseline(&self) -> usize {
+        // `ggdG`, then `i` and the lines separated by `<CR>`, then `<Esc>`
+        fn retype(block: &TextBlock) -> usize {
+            let typed: usize = block.content.iter().map(String::len).sum();
+
+            if typed == 0 && block.len() <= 1 {
+                4
+            } else {
+                typed + block.len() + 5
+            }
+        }
+
+        // `:set ff=dos<CR>` then `:set noeol<CR>`, when the output differs from the input
+        fn options(input: &TextBlock, output: &TextBlock, comparison: &[Comparison]) -> usize {
+            let mut keys = 0;
+
+            if input.fileformat != output.fileformat {
+                keys += ":set ff=".len() + output.fileformat.option().len() + 1;
+            }
+            if input.eol != output.eol && !comparison.contains(&Comparison::TrailingBlankLines) {
+                keys += if output.eol { ":set eol" } else { ":set noeol" }.len() + 1;
+            }
+
+            keys
+        }
+
+        // Each other buffer is reached with `:bn<CR>`
+        retype(&self.output)
+            + options(&self.input, &self.output, &self.comparison)
+            + self
+                .buffers
+                .iter()
+                .map(|buffer| {
+                    4 + retype(&buffer.output)
+                        + options(&buffer.input, &buffer.output, &self.comparison)
+                })
+                .sum::<usize>()
+    }
+
+    /// How `score` compares with the par and the baseline, like `par -2, 40% of the baseline`.
+    pub fn relative_score(&self, score: usize) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(par) = self.par {
+            parts.push(match score as i64 - par as i64 {
+                0 => String::from("at par"),
+                difference => format!("par {:+}", difference),
+            });
+        }
+
+        let baseline = self.baseline();
+        if let Some(percent) = (score * 100 + baseline / 2).checked_div(baseline) {
+            parts.push(format!("{}% of the baseline", percent));
+        }
+
+        parts.join(", ")
+    }
+
+    /// Whether keys are evaluated the same way in both challenges, so that the submissions of one
+    /// hold for the other.
+    pub fn evaluates_like(&self, other: &Self) -> bool {
+        self.input.same_text(&other.input)
+            && self.output.same_text(&other.output)
+            && self.constraints == other.constraints
+            && self.checker == other.checker
+            && self.comparison == other.comparison
+            && self.postconditions == other.postconditions
+            && self.file == other.file
+            && self.layout == other.layout
+            && self.runtime == other.runtime
+            && self.filetype == other.filetype
+            && self.generator == other.generator
+            && self.editor == other.editor
+            && self.buffers.len() == other.buffers.len()
+            && self.buffers.iter().zip(&other.buffers).all(|(new, old)| {
+                new.name == old.name
+                    && new.input.same_text(&old.input)
+                    && new.output.same_text(&old.output)
+            })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == Status::Open
     }
 
-    pub fn add_submission(&mut self, author: String, keys: String, score: usize) -> usize {
+    /// Publishes a pending challenge.
+    ///
+    /// The timestamp is reset to the approval time so that the challenge becomes the last one.
+    pub fn approve(&mut self, timestamp: i64) {
+        self.status = Status::Open;
+        self.timestamp = timestamp;
+    }
+
+    pub fn add_submission(
+        &mut self,
+        author: String,
+        keys: String,
+        score: usize,
+        works_in: Option<Editor>,
+    ) -> usize {
         let sub = Submission {
             score,
             author,
             keys,
+            works_in,
         };
 
         self.scores.push(sub);
@@ -178,27 +1134,596 @@ impl Challenge {
         glob(&format!("{}/*.chal", Self::DIR)).unwrap()
     }
 
-    pub fn last() -> Option<Self> {
+    /// Ids of all the stored challenges.
+    pub fn ids() -> Vec<String> {
         Self::all()
-            .filter_map(|res| {
-                if let Ok(path) = res {
-                    let fname = path.file_stem()?.to_str()?;
-                    fname.parse::<Challenge>().ok()
-                } else {
-                    None
-                }
-            })
+            .filter_map(|res| Some(res.ok()?.file_stem()?.to_str()?.to_owned()))
+            .collect()
+    }
+
+    /// Every name a challenge can be designated by, along with the id it refers to.
+    ///
+    /// This contains the id of each challenge and its slug if it has one.
+    pub fn aliases() -> Vec<(String, String)> {
+        Self::aliases_where(|_| true)
+    }
+
+    /// The aliases of the challenges accepted by `visible`, see `aliases`.
+    ///
+    /// Challenges that can't be read are kept, so that opening them reports why.
+    pub fn aliases_where(visible: impl Fn(&Self) -> bool) -> Vec<(String, String)> {
+        let mut aliases = Vec::new();
+
+        for id in Self::ids() {
+            match Self::open(&id) {
+                Ok(chall) if !visible(&chall) => continue,
+                Ok(Challenge {
+                    slug: Some(slug), ..
+                }) => aliases.push((slug, id.clone())),
+                _ => {}
+            }
+            aliases.push((id.clone(), id));
+        }
+
+        aliases
+    }
+
+    /// Whether `name` can be used as a new id or slug.
+    pub fn is_available(name: &str) -> bool {
+        Self::aliases()
+            .iter()
+            .all(|(alias, _)| !alias.eq_ignore_ascii_case(name))
+    }
+
+    /// Checks that `slug` is a well-formed slug : between 3 and 32 lowercase letters, digits or
+    /// dashes.
+    pub fn is_valid_slug(slug: &str) -> bool {
+        (3..=32).contains(&slug.len())
+            && slug
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }
+
+    /// Derives a challenge id from `seed`, that is not used by any stored challenge.
+    pub fn unique_id(seed: &str) -> String {
+        let taken: Vec<String> = Self::aliases().into_iter().map(|(alias, _)| alias).collect();
+        let mut attempt: u32 = 0;
+
+        loop {
+            let mut hasher = Sha1::new();
+            hasher.update(seed.as_bytes());
+            if attempt > 0 {
+                hasher.update(attempt.to_le_bytes());
+            }
+
+            let mut id = String::with_capacity(6);
+            for elem in hasher.finalize().iter().take(3) {
+                id.push_str(&format!("{:02x}", elem));
+            }
+
+            if !taken.contains(&id) {
+                return id;
+            }
+
+            attempt += 1;
+        }
+    }
+
+    /// Waits until no one else writes challenges, and keeps them from doing so until the lock is
+    /// dropped.
+    ///
+    /// A challenge read again and written under the lock keeps the changes made since it was
+    /// first read. The lock is held on a file, so that the command line tools wait for the bot.
+    pub fn lock() -> Result<StoreLock, ChallengeError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(Self::LOCK)
+            .map_err(ChallengeError::Lock)?;
+        file.lock().map_err(ChallengeError::Lock)?;
+
+        Ok(StoreLock { _file: file })
+    }
+
+    /// Writes a new challenge, failing if its id or its slug is already used by a challenge.
+    ///
+    /// Names are checked again here since they may have been taken since the challenge was made.
+    pub fn create(&self) -> Result<(), ChallengeError> {
+        // Held until the file exists, so that concurrent registrations see each other's names
+        let _lock = Self::lock()?;
+
+        for name in iter::once(&self.id).chain(&self.slug) {
+            if !Self::is_available(name) {
+                return Err(ChallengeError::NameTaken(name.clone()));
+            }
+        }
+
+        let serialize = |source| ChallengeError::Serialize {
+            id: self.id.clone(),
+            source,
+        };
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(Challenge::filename(&self.id))
+            .map_err(|err| serialize(err.into()))?;
+        ron::ser::to_writer(file, self).map_err(serialize)
+    }
+
+    /// Overwrites the stored challenge.
+    pub fn save(&self) -> ron::Result<()> {
+        let file = File::create(Challenge::filename(&self.id))?;
+        ron::ser::to_writer(file, self)
+    }
+
+    /// Opens the challenge stored under exactly `id`.
+    pub fn open(id: &str) -> Result<Self, ChallengeError> {
+        let file = File::open(Challenge::filename(id)).map_err(|source| ChallengeError::Io {
+            id: id.to_owned(),
+            source,
+        })?;
+        ron::de::from_reader(file).map_err(|source| ChallengeError::Deserialize {
+            id: id.to_owned(),
+            source,
+        })
+    }
+
+    /// Resolves `id` to the id of a stored challenge.
+    ///
+    /// An exact match of an id or a slug wins, otherwise `id` has to be the prefix of the names of
+    /// exactly one challenge.
+    pub fn resolve(id: &ChallengeId) -> Result<String, ChallengeError> {
+        Self::resolve_where(id, |_| true)
+    }
+
+    /// Resolves `id` among the challenges accepted by `visible`, see `resolve`.
+    pub fn resolve_where(
+        id: &ChallengeId,
+        visible: impl Fn(&Self) -> bool,
+    ) -> Result<String, ChallengeError> {
+        let aliases = Self::aliases_where(visible);
+
+        if let Some((_, exact)) = aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(id.as_str()))
+        {
+            return Ok(exact.to_owned());
+        }
+
+        let mut matches: Vec<String> = aliases
+            .into_iter()
+            .filter(|(alias, _)| alias.to_ascii_lowercase().starts_with(id.as_str()))
+            .map(|(_, other)| other)
+            .collect();
+        matches.sort();
+        matches.dedup();
+
+        match matches.len() {
+            0 => Err(ChallengeError::NotFound(id.clone())),
+            1 => Ok(matches.remove(0)),
+            _ => Err(ChallengeError::Ambiguous(id.clone(), matches)),
+        }
+    }
+
+    /// Opens the challenge designated by `id`, see `resolve`.
+    pub fn find(id: &ChallengeId) -> Result<Self, ChallengeError> {
+        Self::open(&Self::resolve(id)?)
+    }
+
+    /// Opens the challenge designated by `id` among the ones accepted by `visible`.
+    pub fn find_where(
+        id: &ChallengeId,
+        visible: impl Fn(&Self) -> bool,
+    ) -> Result<Self, ChallengeError> {
+        Self::open(&Self::resolve_where(id, visible)?)
+    }
+
+    /// Whether the user `user` can see this challenge : open challenges are public, pending and
+    /// closed ones are only shown to their author and to moderators.
+    pub fn is_visible_to(&self, user: u64, is_mod: bool) -> bool {
+        is_mod
+            || self.status == Status::Open
+            || self.author.as_ref().is_some_and(|author| author.id == user)
+    }
+
+    pub fn last() -> Option<Self> {
+        Self::ids()
+            .into_iter()
+            .filter_map(|id| Self::open(&id).ok())
+            .filter(|chall| chall.is_open())
             .max_by_key(|chall| chall.timestamp)
     }
+
+    pub fn pending() -> Vec<Self> {
+        let mut pending: Vec<Self> = Self::ids()
+            .into_iter()
+            .filter_map(|id| Self::open(&id).ok())
+            .filter(|chall| chall.status == Status::Pending)
+            .collect();
+
+        pending.sort_by_key(|chall| chall.timestamp);
+        pending
+    }
+}
+
+/// A challenge id, as typed by a user.
+///
+/// Ids are case-insensitive and may be surrounded by backticks. Parsing one does not check that
+/// the challenge exists, use `Challenge::find` for that.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChallengeId(String);
+
+impl ChallengeId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ChallengeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
-impl FromStr for Challenge {
-    type Err = String;
+impl FromStr for ChallengeId {
+    type Err = ChallengeError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.strip_prefix("`").unwrap_or(s);
         let s = s.strip_suffix("`").unwrap_or(s);
 
-        let file = File::open(Challenge::filename(s)).map_err(|e| e.to_string())?;
-        ron::de::from_reader(file).map_err(|e| e.to_string())
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            Ok(ChallengeId(s.to_ascii_lowercase()))
+        } else {
+            Err(ChallengeError::InvalidId(s.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str) -> TextBlock {
+        TextBlock::from_text(None, text)
+    }
+
+    #[test]
+    fn matches_strictly_by_default() {
+        assert!(block("a\nb\n").matches(&block("a\nb\n"), &[]));
+        assert!(!block("a\nb\n").matches(&block("a\nb \n"), &[]));
+        assert!(!block("a\n").matches(&block("a"), &[]));
+        assert!(!block("a\n").matches(&block("a\r\n"), &[]));
+        assert!(!block("a\n").matches(&block("A\n"), &[]));
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let dos = block("a\r\nb\r\n");
+        assert_eq!(dos.content, ["a", "b"]);
+        assert_eq!(dos.fileformat, FileFormat::Dos);
+        assert!(dos.eol);
+
+        let unterminated = block("a\nb");
+        assert_eq!(unterminated.content, ["a", "b"]);
+        assert_eq!(unterminated.fileformat, FileFormat::Unix);
+        assert!(!unterminated.eol);
+
+        // As in nvim, carriage returns stay in the lines unless every line ends with one
+        let mixed = block("a\r\nb\n");
+        assert_eq!(mixed.content, ["a\r", "b"]);
+        assert_eq!(mixed.fileformat, FileFormat::Unix);
+
+        for text in [
+            "",
+            "a\n",
+            "a\nb",
+            "a\r\nb\r\n",
+            "a\r\nb",
+            "a\r\nb\n",
+            "\n\n",
+            "a\r\r\n",
+        ] {
+            assert_eq!(block(text).to_text(), text);
+        }
+    }
+
+    #[test]
+    fn matches_an_empty_buffer_with_an_empty_line() {
+        assert!(block("").matches(&TextBlock::new(None, vec![String::new()]), &[]));
+    }
+
+    #[test]
+    fn tolerates_the_chosen_differences() {
+        let trailing = [Comparison::TrailingWhitespace];
+        assert!(block("a  \nb\t\n").matches(&block("a\nb\n"), &trailing));
+        assert!(!block(" a\n").matches(&block("a\n"), &trailing));
+
+        let blank = [Comparison::TrailingBlankLines];
+        assert!(block("a\n\n  \n").matches(&block("a"), &blank));
+        assert!(!block("\na\n").matches(&block("a\n"), &blank));
+
+        let case = [Comparison::IgnoreCase];
+        assert!(block("Foo\n").matches(&block("fOO\n"), &case));
+
+        let collapse = [Comparison::CollapseWhitespace];
+        assert!(block("  a \t b \n").matches(&block("a b\n"), &collapse));
+        assert!(!block("ab\n").matches(&block("a b\n"), &collapse));
+    }
+
+    fn challenge(input: &str, output: &str) -> Challenge {
+        Challenge::new(
+            String::from("Title"),
+            String::new(),
+            block(input),
+            block(output),
+            String::from("abcdef"),
+            0,
+            Author {
+                id: 0,
+                name: String::from("author"),
+            },
+        )
+    }
+
+    #[test]
+    fn counts_the_naive_solution() {
+        // `ggdG` `i` `ab` `<CR>` `c` `<Esc>`
+        assert_eq!(challenge("x\n", "ab\nc\n").baseline(), 10);
+        // Then `:set ff=dos<CR>`
+        assert_eq!(challenge("x\n", "ab\r\nc\r\n").baseline(), 22);
+        // Then `:set noeol<CR>`
+        assert_eq!(challenge("x\n", "ab\nc").baseline(), 21);
+
+        let mut chall = challenge("x\n", "ab\nc");
+        chall.comparison = vec![Comparison::TrailingBlankLines];
+        assert_eq!(chall.baseline(), 10);
+    }
+
+    #[test]
+    fn scores_relative_to_the_baseline() {
+        let mut chall = challenge("x\n", "ab\nc\n");
+        chall.par = Some(4);
+
+        assert_eq!(chall.relative_score(5), "par +1, 50% of the baseline");
+        assert_eq!(chall.relative_score(4), "at par, 40% of the baseline");
+    }
+
+    fn breaks(chall: &Challenge, rule: fn(&ChallengeError) -> bool) -> bool {
+        chall.validate().iter().any(rule)
+    }
+
+    #[test]
+    fn validates_challenges() {
+        assert!(challenge("a\n", "b\n").validate().is_empty());
+
+        assert!(breaks(&challenge("a\n", "a\n"), |err| matches!(
+            err,
+            ChallengeError::Trivial
+        )));
+        assert!(breaks(&challenge(" \n", "b\n"), |err| matches!(
+            err,
+            ChallengeError::EmptyBlock { what: "input" }
+        )));
+
+        let mut chall = challenge("a\n", "b\n");
+        chall.title = "t".repeat(Challenge::MAX_TITLE + 1);
+        assert!(breaks(&chall, |err| matches!(
+            err,
+            ChallengeError::TooLarge { what: "title", .. }
+        )));
+
+        let many_lines = "a\n".repeat(Challenge::MAX_LINES + 1);
+        assert!(breaks(&challenge(&many_lines, "b\n"), |err| matches!(
+            err,
+            ChallengeError::TooLarge { what: "input", .. }
+        )));
+
+        let long_line = "b".repeat(Challenge::MAX_BYTES + 1);
+        assert!(breaks(&challenge("a\n", &long_line), |err| matches!(
+            err,
+            ChallengeError::TooLarge { what: "output", .. }
+        )));
+
+        let mut chall = challenge("a\n", "b\n");
+        chall.input.lang = Some(String::from("klingon"));
+        assert!(breaks(&chall, |err| matches!(
+            err,
+            ChallengeError::UnknownLang(_)
+        )));
+
+        for lang in ["text", "txt", "plaintext", "Rust"] {
+            chall.input.lang = Some(String::from(lang));
+            assert!(chall.validate().is_empty(), "{}", lang);
+        }
+
+        let mut chall = challenge("a\n", "b\n");
+        chall.editor = Some(Editor::Vim);
+        assert_eq!(
+            breaks(&chall, |err| matches!(err, ChallengeError::VimUnavailable)),
+            !vim::is_available()
+        );
+
+        chall.checker = Some(String::from("return true"));
+        assert!(breaks(&chall, |err| matches!(
+            err,
+            ChallengeError::NeovimOnly
+        )));
+    }
+
+    #[test]
+    fn combines_differences() {
+        let comparison = Comparison::from_setting("ignore-case trailing-blank-lines").unwrap();
+
+        assert!(block("A\n\n").matches(&block("a"), &comparison));
+        assert!(Comparison::from_setting("strict").unwrap().is_empty());
+        assert!(Comparison::from_setting("loose").is_err());
+    }
+
+    /// A stored challenge named `id`, and `slug` if given.
+    fn named(id: &str, slug: Option<&str>) -> Challenge {
+        let mut chall = challenge("a\n", "b\n");
+        chall.id = String::from(id);
+        chall.slug = slug.map(String::from);
+        chall
+    }
+
+    /// Runs `test` in a new directory holding the `stored` challenges.
+    ///
+    /// Challenges are stored relative to the current directory, which is shared by the tests, so
+    /// they run one at a time.
+    fn in_store(stored: &[Challenge], test: impl FnOnce()) {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Mutex, PoisonError};
+        use std::{env, fs, process};
+
+        static CURRENT_DIR: Mutex<()> = Mutex::new(());
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let _current_dir = CURRENT_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let dir = env::temp_dir().join(format!(
+            "vim-golf-bot-test-{}-{}",
+            process::id(),
+            RUNS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(dir.join(Challenge::DIR)).unwrap();
+        let previous = env::current_dir().unwrap();
+        env::set_current_dir(&dir).unwrap();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            for chall in stored {
+                chall.create().unwrap();
+            }
+            test()
+        }));
+
+        env::set_current_dir(previous).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        if let Err(panic) = result {
+            panic::resume_unwind(panic);
+        }
+    }
+
+    fn id(id: &str) -> ChallengeId {
+        id.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ids() {
+        assert_eq!(id("`AbC-1`").as_str(), "abc-1");
+        assert!(matches!(
+            "a b".parse::<ChallengeId>(),
+            Err(ChallengeError::InvalidId(_))
+        ));
+        assert!(matches!(
+            "``".parse::<ChallengeId>(),
+            Err(ChallengeError::InvalidId(_))
+        ));
+    }
+
+    #[test]
+    fn resolves_exact_names_before_prefixes() {
+        let stored = [
+            named("abc", None),
+            named("abcd", Some("reverse")),
+            named("ffffff", Some("reverse-lines")),
+        ];
+
+        in_store(&stored, || {
+            assert_eq!(Challenge::resolve(&id("abc")).unwrap(), "abc");
+            assert_eq!(Challenge::resolve(&id("ABCD")).unwrap(), "abcd");
+            assert_eq!(Challenge::resolve(&id("reverse")).unwrap(), "abcd");
+            assert_eq!(Challenge::resolve(&id("reverse-l")).unwrap(), "ffffff");
+            assert_eq!(Challenge::resolve(&id("fff")).unwrap(), "ffffff");
+        });
+    }
+
+    #[test]
+    fn reports_ambiguous_and_unknown_ids() {
+        let stored = [named("abc", None), named("abd", Some("xyz"))];
+
+        in_store(&stored, || {
+            match Challenge::resolve(&id("ab")) {
+                Err(ChallengeError::Ambiguous(_, matches)) => assert_eq!(matches, ["abc", "abd"]),
+                _ => panic!("`ab` should be ambiguous"),
+            }
+            assert!(matches!(
+                Challenge::resolve(&id("zzz")),
+                Err(ChallengeError::NotFound(_))
+            ));
+
+            // Hidden challenges are not found, even by their exact id
+            let open = |chall: &Challenge| chall.is_open();
+            assert!(matches!(
+                Challenge::resolve_where(&id("abc"), open),
+                Err(ChallengeError::NotFound(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn resolves_corrupt_challenges_to_report_them() {
+        in_store(&[], || {
+            std::fs::write(Challenge::filename("bad"), "not a challenge").unwrap();
+
+            assert_eq!(Challenge::resolve(&id("ba")).unwrap(), "bad");
+            assert!(matches!(
+                Challenge::find(&id("bad")),
+                Err(ChallengeError::Deserialize { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn checks_slugs() {
+        assert!(Challenge::is_valid_slug("abc"));
+        assert!(Challenge::is_valid_slug("reverse-lines-2"));
+        assert!(Challenge::is_valid_slug(&"a".repeat(32)));
+        assert!(!Challenge::is_valid_slug("ab"));
+        assert!(!Challenge::is_valid_slug(&"a".repeat(33)));
+        assert!(!Challenge::is_valid_slug("Reverse"));
+        assert!(!Challenge::is_valid_slug("reverse_lines"));
+        assert!(!Challenge::is_valid_slug("lignes-inversées"));
+    }
+
+    #[test]
+    fn takes_names_regardless_of_case() {
+        in_store(&[named("abcdef", Some("reverse"))], || {
+            assert!(!Challenge::is_available("reverse"));
+            assert!(!Challenge::is_available("Reverse"));
+            assert!(!Challenge::is_available("ABCDEF"));
+            assert!(Challenge::is_available("reverse-lines"));
+
+            assert!(matches!(
+                named("abcdef", None).create(),
+                Err(ChallengeError::NameTaken(_))
+            ));
+            assert!(matches!(
+                named("fedcba", Some("REVERSE")).create(),
+                Err(ChallengeError::NameTaken(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn derives_ids_that_are_not_taken() {
+        in_store(&[], || {
+            let first = Challenge::unique_id("seed");
+            assert_eq!(first.len(), 6);
+            assert_eq!(Challenge::unique_id("seed"), first);
+
+            // The id is taken as the slug of another challenge
+            named("abcdef", Some(&first)).create().unwrap();
+            let second = Challenge::unique_id("seed");
+            assert_ne!(second, first);
+
+            named(&second, None).create().unwrap();
+            let third = Challenge::unique_id("seed");
+            assert!(Challenge::is_available(&third));
+            assert!(third != first && third != second);
+        });
     }
 }
@@ -0,0 +1,183 @@
+//! Maintenance commands, run from the command line instead of starting the bot.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use vim_golf_bot::audit::{Action, Entry};
+use vim_golf_bot::challenge::{Author, Challenge, ChallengeId};
+use vim_golf_bot::engine;
+use vim_golf_bot::solver::{self, Limits};
+
+/// Runs the subcommand named by the first argument, returns whether there was one.
+pub async fn run(args: &[String]) -> bool {
+    match args.split_first() {
+        Some((command, rest)) if command == "revalidate" => {
+            revalidate(rest).await;
+            true
+        }
+        Some((command, rest)) if command == "solve" => {
+            solve(rest).await;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The moderator recorded in the audit log for actions run from the command line.
+fn console() -> Author {
+    Author {
+        id: 0,
+        name: String::from("command line"),
+    }
+}
+
+/// The ids of the challenges named by `names`, every challenge if there are none.
+fn resolve_all(names: Vec<&String>) -> Vec<String> {
+    if names.is_empty() {
+        return Challenge::ids();
+    }
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            match name
+                .parse::<ChallengeId>()
+                .and_then(|id| Challenge::resolve(&id))
+            {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    eprintln!("Skipping {} : {}", name, err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// `revalidate [--apply] [challenge id...]`, see the `revalidate` command.
+async fn revalidate(args: &[String]) {
+    let apply = args.iter().any(|arg| arg == "--apply");
+
+    let names: Vec<&String> = args.iter().filter(|arg| *arg != "--apply").collect();
+    let ids = resolve_all(names);
+
+    let mut changed = 0;
+
+    for id in ids {
+        let chall = match Challenge::open(&id) {
+            Ok(chall) => chall,
+            Err(err) => {
+                eprintln!("Skipping {} : {}", id, err);
+                continue;
+            }
+        };
+
+        let changes = match engine::revalidate(&chall).await {
+            Ok(changes) => changes,
+            Err(err) => {
+                eprintln!("Could not evaluate {} : {}", id, err);
+                continue;
+            }
+        };
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        println!("{} :", chall.id);
+        for change in &changes {
+            println!("* {}", change);
+        }
+        changed += changes.len();
+
+        if apply {
+            match engine::apply_to_stored(&chall, &changes) {
+                Ok(true) => {}
+                Ok(false) => {
+                    eprintln!("{} has been edited in the meantime, skipping it", chall.id);
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Could not save {} : {}", chall.id, err);
+                    continue;
+                }
+            }
+
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or_default();
+            let entry = Entry::new(
+                timestamp,
+                console(),
+                Action::Revalidate {
+                    challenge: chall.id.clone(),
+                    changes,
+                },
+            );
+
+            if let Err(err) = entry.record() {
+                eprintln!("Could not record the changes of {} : {}", chall.id, err);
+            }
+        }
+    }
+
+    if changed == 0 {
+        println!("Every submission is still valid, with the same score.");
+    } else if !apply {
+        println!("Nothing has been changed yet, run again with --apply to apply these changes.");
+    }
+}
+
+/// `solve [--depth n] [--width n] [challenge id...]`, see the `probe` command.
+async fn solve(args: &[String]) {
+    let mut limits = Limits::default();
+    let mut names = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let bound = match arg.as_str() {
+            "--depth" => &mut limits.depth,
+            "--width" => &mut limits.width,
+            _ => {
+                names.push(arg);
+                continue;
+            }
+        };
+
+        match args.next().and_then(|value| value.parse().ok()) {
+            Some(value) => *bound = value,
+            None => {
+                eprintln!("{} expects a number", arg);
+                return;
+            }
+        }
+    }
+
+    for id in resolve_all(names) {
+        let chall = match Challenge::open(&id) {
+            Ok(chall) => chall,
+            Err(err) => {
+                eprintln!("Skipping {} : {}", id, err);
+                continue;
+            }
+        };
+
+        match solver::solve(&chall, &limits).await {
+            Ok(search) => match search.solution {
+                Some(solution) => println!(
+                    "{} : `{}` ({} keys, {}), {} sequences tried",
+                    chall.id,
+                    solution.keys,
+                    solution.score,
+                    chall.relative_score(solution.score),
+                    search.explored
+                ),
+                None => println!(
+                    "{} : no solution within {} commands, {} sequences tried",
+                    chall.id, limits.depth, search.explored
+                ),
+            },
+            Err(err) => eprintln!("Could not search {} : {}", id, err),
+        }
+    }
+}
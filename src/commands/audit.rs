@@ -0,0 +1,147 @@
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use log::{error, info};
+
+use vim_golf_bot::audit::{Action, Entry};
+use vim_golf_bot::challenge::{Author, Challenge, ChallengeError, ChallengeId};
+use vim_golf_bot::config::Config;
+
+use super::say_lines;
+
+/// Records an action made by the author of `msg`, and mirrors it in the mod-log channel.
+pub async fn record(ctx: &Context, msg: &Message, action: Action) -> CommandResult {
+    let entry = Entry::new(
+        msg.timestamp.timestamp(),
+        Author {
+            id: msg.author.id.0,
+            name: msg.author.name.to_string(),
+        },
+        action,
+    );
+
+    info!("{} {}", entry.moderator.name, entry.action);
+    entry.record()?;
+
+    if let Some(channel) = Config::load().mod_log {
+        let content = MessageBuilder::new()
+            .push_bold(&entry.moderator.name)
+            .push(" ")
+            .push(entry.action.to_string())
+            .build();
+
+        if let Err(why) = ChannelId(channel).say(ctx, content).await {
+            error!("Could not write to the mod-log channel : {}", why);
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Prints the last moderator actions, optionally only the ones about a challenge."]
+#[usage = "[challenge id]"]
+#[min_args(0)]
+#[max_args(1)]
+async fn audit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    const SHOWN: usize = 10;
+    // Most characters shown of an action, since a revalidation can list many changes
+    const MAX_ACTION: usize = 300;
+
+    let id = if args.is_empty() {
+        None
+    } else {
+        let id = args.single::<ChallengeId>()?;
+
+        // Rejected challenges can't be resolved anymore, they are only found by their id
+        Some(match Challenge::resolve(&id) {
+            Ok(resolved) => resolved,
+            Err(err @ ChallengeError::Ambiguous(..)) => {
+                msg.reply(ctx, format!("Impossible to open this challenge : {}", err))
+                    .await?;
+                return Err(err.into());
+            }
+            Err(_) => id.as_str().to_owned(),
+        })
+    };
+
+    let entries: Vec<Entry> = Entry::all()
+        .into_iter()
+        .filter(|entry| match &id {
+            Some(id) => entry.action.challenge_id() == Some(id.as_str()),
+            None => true,
+        })
+        .collect();
+
+    let mut lines = Vec::new();
+
+    if entries.is_empty() {
+        lines.push(String::from("No moderator action recorded."));
+    } else {
+        lines.push(String::from("The last moderator actions are :"));
+
+        for entry in entries.iter().rev().take(SHOWN).rev() {
+            let mut action = entry.action.to_string();
+            if action.chars().count() > MAX_ACTION {
+                action = action.chars().take(MAX_ACTION).collect();
+                action.push('…');
+            }
+
+            lines.push(format!(
+                "* <t:{}:f> **{}** {}",
+                entry.timestamp, entry.moderator.name, action
+            ));
+        }
+    }
+
+    say_lines(&ctx.http, msg.channel_id, &lines).await
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Sets the channel where moderator actions are mirrored, disables it without argument."]
+#[usage = "[channel]"]
+#[min_args(0)]
+#[max_args(1)]
+async fn modlog(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let channel = if args.is_empty() {
+        None
+    } else {
+        Some(args.single::<ChannelId>()?)
+    };
+
+    let mut config = Config::load();
+    config.mod_log = channel.map(|c| c.0);
+    config.save()?;
+
+    let value = match channel {
+        Some(channel) => channel.mention(),
+        None => String::from("none"),
+    };
+
+    record(
+        ctx,
+        msg,
+        Action::Config {
+            key: String::from("mod-log channel"),
+            value: value.clone(),
+        },
+    )
+    .await?;
+
+    if channel.is_some() {
+        msg.reply(
+            ctx,
+            format!("Moderator actions are now mirrored to {}.", value),
+        )
+        .await?;
+    } else {
+        msg.reply(ctx, "Moderator actions are not mirrored anymore.")
+            .await?;
+    }
+
+    Ok(())
+}
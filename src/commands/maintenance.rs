@@ -0,0 +1,54 @@
+use serenity::framework::standard::{macros::command, CommandResult};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use log::error;
+
+use vim_golf_bot::fsck::{self, Finding};
+
+use super::say_lines;
+
+fn summary(findings: &[Finding]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "The integrity check found {} problems :",
+        findings.len()
+    )];
+    lines.extend(findings.iter().map(|finding| format!("* {}", finding)));
+
+    lines
+}
+
+/// Sends the problems found by the integrity check to the owner of the bot.
+pub async fn report_to_owner(http: &Http, findings: &[Finding]) -> CommandResult {
+    let owner = http.get_current_application_info().await?.owner.id;
+    let channel = owner.create_dm_channel(http).await?;
+
+    say_lines(http, channel.id, &summary(findings)).await
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Checks the stored challenges, and moves the unusable files to quarantine."]
+#[usage = ""]
+#[num_args(0)]
+async fn fsck(ctx: &Context, msg: &Message) -> CommandResult {
+    let findings = fsck::check();
+
+    if findings.is_empty() {
+        msg.reply(ctx, "Every challenge is fine.").await?;
+        return Ok(());
+    }
+
+    for finding in &findings {
+        error!("{}", finding);
+    }
+
+    say_lines(&ctx.http, msg.channel_id, &summary(&findings)).await?;
+
+    if let Err(why) = report_to_owner(&ctx.http, &findings).await {
+        error!("Could not warn the owner : {}", why);
+    }
+
+    Ok(())
+}
@@ -1,34 +1,46 @@
-use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::framework::standard::{
+    macros::command, ArgError, Args, CommandError, CommandResult, Delimiter,
+};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 
-use sha1::{Digest, Sha1};
+use log::error;
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::str::Lines;
-use vim_golf_bot::challenge::{Challenge, FromLines, TextBlock};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use vim_golf_bot::audit::Action;
+use vim_golf_bot::challenge::{Author, Challenge, ChallengeError, Status, Submission, TextBlock};
+use vim_golf_bot::engine;
+use vim_golf_bot::parser::Draft;
+use vim_golf_bot::solver::{self, Limits};
 
-fn extract_content(lines: &mut Lines) -> Result<(String, String, TextBlock, TextBlock), String> {
-    lines.next();
+use super::audit::record;
+use super::reports::{refresh_board, render_challenge};
+use super::{challenge_arg, is_moderator, say_lines};
+use crate::queue::{EvaluationQueue, Refusal, Ticket};
 
-    let mut content = lines.filter(|line| !line.is_empty());
+const CONFIRM: char = '✅';
+const CANCEL: char = '❌';
 
-    let line = content.next().ok_or(String::from("Challenge is empty"))?;
-
-    let first: String;
-    if let Some(end) = line.strip_prefix("# ") {
-        first = String::from(end);
-    } else {
-        return Err(String::from("Missing title"));
-    }
+/// A challenge waiting for its author to confirm its registration.
+pub struct Preview {
+    author: UserId,
+    challenge: Challenge,
+    created: Instant,
+}
 
-    let desc = String::from_lines(lines).map_err(|_| String::from("Missing description"))?;
+/// The previews, by the id of the message showing them.
+pub struct Previews;
 
-    let input = TextBlock::from_lines(lines).map_err(|_| String::from("Missing input"))?;
-    let output = TextBlock::from_lines(lines).map_err(|_| String::from("Mising output"))?;
+impl Previews {
+    pub const TIMEOUT: Duration = Duration::from_secs(15 * 60);
+}
 
-    Ok((first, desc, input, output))
+impl TypeMapKey for Previews {
+    type Value = Arc<Mutex<HashMap<MessageId, Preview>>>;
 }
 
 #[command]
@@ -39,100 +51,962 @@ The argument for this function is actually a text, describing the challenge.
 The format should be :
 
 ```
-register
+register [name]
 
 # Challenge Title
 
-## Short Description
+Description of the challenge, in as many paragraphs as needed.
 
-Input:
+## Input
 [MARKDOWN CODE BLOCK CONTAINING THE INPUT]
 
-Output:
-[MARKDOWN CODE BLOCK CONTAINING THE INPUT]
+## Output
+[MARKDOWN CODE BLOCK CONTAINING THE OUTPUT]
+
+## Hints
+- [OPTIONAL HINTS, SHOWN AS SPOILERS]
+
+## Settings
+- name = [OPTIONAL NAME]
 ```
 
-The code block should by separated in triple backticks (as any markdown code block).
+Sections can also be introduced by a line like `Input:`.
+The code blocks can be fenced with backticks or tildes, as any markdown code block.
+
+Long challenges can be attached instead, either as a `.md` file in the same format,
+or as `input` and `output` files (like `input.txt` or `output.rs`) along with the rest of the
+challenge in the message.
+They can also be attached as a `.toml` file with `title`, `description` and `hints` keys,
+`[input]` and `[output]` tables with `content` and an optional `lang`, and a `[settings]` table.
+
+The optional name (like `reverse-lines`) can be used in place of the challenge id.
+
+The settings can also restrict how the challenge can be solved :
+- `forbid = q @` forbids typing any of these keys
+- `forbid-commands = substitute global` forbids these ex commands, by their full name, along
+  with the commands running other ones or evaluating expressions, like `:normal` or `:call`, and
+  the expression register
+- `forbid-modes = insert visual` forbids entering these modes, among insert, replace, visual,
+  cmdline and search
+- `ex-only = true` only allows ex commands, each typed after `:` and ended by `<CR>`
+
+Outputs have to match the expected one exactly, unless some differences are tolerated with
+`compare = trailing-whitespace trailing-blank-lines`, among trailing-whitespace,
+trailing-blank-lines, ignore-case and collapse-whitespace.
+
+Challenges can span several buffers, each in a section like `## Buffer notes.txt` containing its
+input code block, then its output code block, and every output has to match.
+The buffer of the input can be named with `file = main.txt`, and the other buffers are hidden
+unless `layout` is `split`, `vsplit` or `tabs`.
+
+To prevent solutions fitted to the input, a `## Generator` section can hold Lua code returning
+the lines of another input and of its output, as two tables.
+The keys then also have to solve each generated input, the code getting its `index` and
+`math.random` being seeded from `seed = 42`, so that the same inputs are generated each time.
+The number of inputs is set with `cases = 5`, and the code can also be attached as a
+`generator.lua` file, or given as a `generator` key in a `.toml` file.
+It runs with the same limits as a checker, described below.
+
+The bot shows scores relative to a naive solution deleting everything then typing the output,
+and to a par score chosen by the author with `par = 12`.
+
+The editor starts without any configuration, unless parts of nvim's runtime are loaded with
+`runtime = ftplugin indent syntax`, for the filetype given by the language of each code block.
+The filetype of the input can also be set with `filetype = rust`.
+
+Submissions are run in Neovim, and also in Vim when the challenge only compares outputs.
+Keys working in either editor are accepted, unless `editor = vim` requires them to work in Vim,
+`editor = neovim` in Neovim, or `editor = both` in both.
+
+The settings can also require the keys to leave the editor in a given state :
+- `cursor = 3 5` requires the cursor to end on line 3, column 5 (the column is optional)
+- `mode = insert` requires ending in a mode, among normal, insert, replace, visual and select
+- `register = a foo` requires the register `a` to hold `foo`, lines being joined by `\n`
+
+When several outputs are valid, a `## Checker` section can hold a Lua code block deciding whether
+an output is valid, the output then being only an example.
+The code gets the `lines` of the output and the `input` lines, and returns whether the output is
+valid, optionally followed by a message explaining why it is not.
+It runs for 5 seconds at most, with only the `string`, `table` and `math` libraries and pure
+helpers like `vim.split` or `vim.json`.
+It can also be attached as a `checker.lua` file, or given as a `checker` key in a `.toml` file.
+
+The bot shows a preview of the challenge, which has to be confirmed with a reaction.
+The challenge will then be published once a moderator approves it.
+It can still be modified afterwards with `edit`.
 "##]
 async fn register(ctx: &Context, msg: &Message) -> CommandResult {
-    match extract_content(&mut msg.content.lines()) {
-        Ok((title, desc, input_lines, output_lines)) => {
-            // Create unique challenge name
-            let mut hasher = Sha1::new();
+    let (words, body) = split_command(msg, "register");
+    let draft = read_draft(ctx, msg, body).await?;
 
-            hasher.update(msg.author.name.as_bytes());
-            hasher.update(msg.timestamp.to_string().as_bytes());
-            hasher.update(title.as_bytes());
+    // The optional slug follows the command name on the first line
+    let slug = words
+        .first()
+        .copied()
+        .or_else(|| draft.setting("name"))
+        .map(|slug| slug.to_ascii_lowercase());
 
-            let mut chal_id = String::with_capacity(6);
+    if let Some(slug) = &slug {
+        if !check_slug(ctx, msg, slug).await? {
+            return Ok(());
+        }
+    }
 
-            for elem in hasher.finalize().iter().take(3) {
-                chal_id.push_str(&format!("{:02x}", elem));
-            }
+    let chall = new_challenge(msg, draft, slug);
+
+    propose(ctx, msg, chall).await
+}
+
+/// Splits the content of `msg` in the words following `command` on its first line, and the rest
+/// of the message.
+///
+/// The rest starts with a line break, so that line numbers in it match the message.
+fn split_command<'a>(msg: &'a Message, command: &str) -> (Vec<&'a str>, &'a str) {
+    let (first_line, body) = msg
+        .content
+        .split_at(msg.content.find('\n').unwrap_or_else(|| msg.content.len()));
+
+    let words = first_line
+        .split_whitespace()
+        .skip_while(|word| !word.ends_with(command))
+        .skip(1)
+        .collect();
+
+    (words, body)
+}
+
+/// Most bytes downloaded from the attached files, one block of `Challenge::MAX_BYTES` for the
+/// input, the output, the buffers, the checker and the generator, and one for the rest.
+const MAX_ATTACHED: u64 = (Challenge::MAX_BYTES * (2 * (Challenge::MAX_BUFFERS + 1) + 3)) as u64;
 
-            let chall = Challenge::new(
-                title,
-                desc,
-                input_lines,
-                output_lines,
-                chal_id,
-                msg.timestamp.timestamp(),
-            );
+/// Reads the challenge written in `body` or attached to `msg`, replying when it is invalid.
+async fn read_draft(ctx: &Context, msg: &Message, body: &str) -> Result<Draft, CommandError> {
+    let attached: u64 = msg
+        .attachments
+        .iter()
+        .map(|attachment| attachment.size)
+        .sum();
+    if attached > MAX_ATTACHED {
+        let err = ChallengeError::TooLarge {
+            what: "attached files",
+            limit: format!("{} bytes", MAX_ATTACHED),
+        };
+        msg.reply(ctx, format!("Invalid vim golf challenge, {}.", err))
+            .await?;
+        return Err(CommandError::from(err));
+    }
 
-            let file = File::create(Challenge::filename(&chall.id))?;
-            ron::ser::to_writer(file, &chall)?;
+    let ignored = !body.trim().is_empty()
+        && msg
+            .attachments
+            .iter()
+            .any(|attachment| Draft::is_challenge_file(&attachment.filename));
+    if ignored {
+        msg.reply(
+            ctx,
+            "The challenge is read from the attached file, the text of the message is ignored.",
+        )
+        .await?;
+    }
+
+    let mut files = Vec::with_capacity(msg.attachments.len());
+    for attachment in &msg.attachments {
+        files.push((attachment.filename.clone(), attachment.download().await?));
+    }
 
+    match Draft::from_attachments(body, files) {
+        Ok(draft) => Ok(draft),
+        Err(err) => {
             msg.reply(
                 ctx,
                 format!(
-                    "Thanks for your submission, your challenge id is `{}`",
-                    chall.id
+                    "Invalid vim golf challenge, {}.\nUse `?help register` to see the expected format.",
+                    err
                 ),
             )
             .await?;
-            Ok(())
+            Err(CommandError::from(err))
         }
-        Err(err) => {
-            msg.reply(ctx, format!("Invalid vim golf challenge : {}.", err))
+    }
+}
+
+/// Checks that `slug` can be given to a new challenge, replying when it can't.
+async fn check_slug(ctx: &Context, msg: &Message, slug: &str) -> Result<bool, CommandError> {
+    if !Challenge::is_valid_slug(slug) {
+        msg.reply(
+            ctx,
+            "Invalid challenge name : it should be 3 to 32 letters, digits or dashes.",
+        )
+        .await?;
+        Ok(false)
+    } else if !Challenge::is_available(slug) {
+        msg.reply(ctx, format!("The `{}` name is already taken.", slug))
+            .await?;
+        Ok(false)
+    } else {
+        Ok(true)
+    }
+}
+
+/// Creates a challenge from `draft`, written by the author of `msg`.
+fn new_challenge(msg: &Message, draft: Draft, slug: Option<String>) -> Challenge {
+    // Create unique challenge name
+    let chal_id = Challenge::unique_id(&format!(
+        "{}{}{}",
+        msg.author.name, msg.timestamp, draft.title
+    ));
+
+    let mut chall = Challenge::new(
+        draft.title.clone(),
+        draft.description.clone(),
+        draft.input.clone(),
+        draft.output.clone(),
+        chal_id,
+        msg.timestamp.timestamp(),
+        Author {
+            id: msg.author.id.0,
+            name: msg.author.name.to_string(),
+        },
+    );
+    chall.slug = slug;
+    apply_draft(&mut chall, draft);
+
+    chall
+}
+
+/// Replaces the content and the settings of `chall` with the ones of `draft`, except its name.
+fn apply_draft(chall: &mut Challenge, draft: Draft) {
+    // The settings are read before the draft is taken apart
+    chall.constraints = draft.constraints();
+    chall.comparison = draft.comparison();
+    chall.postconditions = draft.postconditions();
+    chall.file = draft.file();
+    chall.layout = draft.layout();
+    chall.runtime = draft.runtime();
+    chall.filetype = draft.setting("filetype").map(String::from);
+    chall.generator = draft.to_generator();
+    chall.par = draft.setting("par").and_then(|par| par.parse().ok());
+    chall.editor = draft.editor();
+
+    chall.title = draft.title;
+    chall.description = draft.description;
+    chall.input = draft.input;
+    chall.output = draft.output;
+    chall.hints = draft.hints;
+    chall.checker = draft.checker;
+    chall.buffers = draft.buffers;
+}
+
+/// Replies with the problems found by `Challenge::validate`, returns whether there were any.
+async fn report_problems(
+    ctx: &Context,
+    msg: &Message,
+    chall: &Challenge,
+    header: &str,
+) -> Result<bool, CommandError> {
+    let problems = chall.validate();
+    if problems.is_empty() {
+        return Ok(false);
+    }
+
+    let mut builder = MessageBuilder::new();
+    builder.push_line(header);
+
+    for problem in problems {
+        builder.push("* ").push_line(problem.to_string());
+    }
+
+    msg.reply(ctx, builder.build()).await?;
+    Ok(true)
+}
+
+/// Shows a preview of `chall`, which is registered once its author confirms it.
+async fn propose(ctx: &Context, msg: &Message, chall: Challenge) -> CommandResult {
+    if report_problems(ctx, msg, &chall, "Your challenge can't be registered :").await? {
+        return Ok(());
+    }
+
+    let preview = msg.channel_id.say(ctx, render_challenge(&chall)).await?;
+    preview.react(ctx, CONFIRM).await?;
+    preview.react(ctx, CANCEL).await?;
+
+    let previews = ctx
+        .data
+        .read()
+        .await
+        .get::<Previews>()
+        .cloned()
+        .expect("Previews are not initialized");
+
+    if let Ok(mut previews) = previews.lock() {
+        previews.retain(|_, preview| preview.created.elapsed() < Previews::TIMEOUT);
+        previews.insert(
+            preview.id,
+            Preview {
+                author: msg.author.id,
+                challenge: chall,
+                created: Instant::now(),
+            },
+        );
+    }
+
+    msg.reply(
+        ctx,
+        format!(
+            "This is how your challenge will be shown. React with {} to submit it, or with {} to \
+            cancel, within {} minutes.",
+            CONFIRM,
+            CANCEL,
+            Previews::TIMEOUT.as_secs() / 60
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Sends `chall` as a markdown file, to be modified and sent back.
+async fn send_template(
+    ctx: &Context,
+    msg: &Message,
+    chall: &Challenge,
+    content: String,
+) -> CommandResult {
+    let template = Draft::from(chall).to_markdown();
+    let filename = format!("{}.md", chall.name());
+
+    // The template may hold hidden parts, like the checker, it is only sent to the author of `msg`
+    let channel = msg.author.create_dm_channel(ctx).await?;
+    channel
+        .send_files(ctx, vec![(template.as_bytes(), filename.as_str())], |m| {
+            m.content(content)
+        })
+        .await?;
+
+    if !msg.is_private() {
+        msg.reply(
+            ctx,
+            "The challenge has been sent to you in a private message.",
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Registers or drops the challenge previewed in the message `reaction` is on.
+pub async fn confirm_registration(ctx: &Context, reaction: &Reaction) -> CommandResult {
+    let confirmed = match &reaction.emoji {
+        ReactionType::Unicode(emoji) if emoji.starts_with(CONFIRM) => true,
+        ReactionType::Unicode(emoji) if emoji.starts_with(CANCEL) => false,
+        _ => return Ok(()),
+    };
+
+    let previews = ctx
+        .data
+        .read()
+        .await
+        .get::<Previews>()
+        .cloned()
+        .expect("Previews are not initialized");
+
+    let preview = match previews.lock() {
+        Ok(mut previews) => match previews.get(&reaction.message_id) {
+            Some(preview) if preview.author == reaction.user_id => {
+                previews.remove(&reaction.message_id)
+            }
+            _ => None,
+        },
+        Err(_) => None,
+    };
+
+    let preview = match preview {
+        Some(preview) => preview,
+        None => return Ok(()),
+    };
+
+    let author = reaction.user_id.mention();
+
+    if preview.created.elapsed() >= Previews::TIMEOUT {
+        reaction
+            .channel_id
+            .say(
+                ctx,
+                format!(
+                    "{} this preview expired, please register your challenge again.",
+                    author
+                ),
+            )
+            .await?;
+        return Ok(());
+    } else if !confirmed {
+        reaction
+            .channel_id
+            .say(
+                ctx,
+                format!("{} your challenge has not been registered.", author),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let chall = preview.challenge;
+
+    match chall.create() {
+        Ok(()) => {}
+        Err(ChallengeError::NameTaken(name)) => {
+            reaction
+                .channel_id
+                .say(
+                    ctx,
+                    format!(
+                        "{} the `{}` name has been taken in the meantime.",
+                        author, name
+                    ),
+                )
                 .await?;
-            Err::<(), CommandError>(CommandError::from(err))
+            return Ok(());
         }
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut answer = format!(
+        "{} thanks for your submission, your challenge id is `{}`",
+        author, chall.id
+    );
+    if let Some(slug) = &chall.slug {
+        answer.push_str(&format!(" (or `{}`)", slug));
+    }
+    answer.push_str(". It will be published once a moderator reviewed it.");
+
+    reaction.channel_id.say(ctx, answer).await?;
+
+    Ok(())
+}
+
+/// Reserves a worker of the evaluation queue for the author of `msg`.
+///
+/// Replies and returns `None` when they already have an evaluation in flight or just had one,
+/// telling them what is `dropped` because of it, if anything.
+async fn worker_ticket(
+    ctx: &Context,
+    msg: &Message,
+    dropped: Option<&str>,
+) -> Result<Option<Ticket>, CommandError> {
+    let queue = ctx
+        .data
+        .read()
+        .await
+        .get::<EvaluationQueue>()
+        .cloned()
+        .expect("Evaluation queue is not initialized");
+
+    let reason = match EvaluationQueue::admit(&queue, msg.author.id) {
+        Ok(ticket) => return Ok(Some(ticket)),
+        Err(Refusal::Busy) => {
+            String::from("Please wait for your previous evaluation to be finished")
+        }
+        Err(Refusal::Cooldown(delay)) => format!(
+            "You are evaluating too fast, please retry in {} seconds",
+            delay.as_secs() + 1
+        ),
+    };
+
+    let reply = match dropped {
+        Some(dropped) => format!("{}, {}.", reason, dropped),
+        None => format!("{}.", reason),
+    };
+    msg.reply(ctx, reply).await?;
+
+    Ok(None)
+}
+
+/// Keeps the line endings of `old` when `new` has the same lines, as code blocks can't hold them.
+fn keep_line_endings(new: &mut TextBlock, old: &TextBlock) {
+    if new.content == old.content {
+        new.fileformat = old.fileformat;
+        new.eol = old.eol;
     }
 }
 
+#[command]
+#[description = r##"Edits a challenge, only its author and moderators can do so.
+
+Without a text, the challenge is sent as a file to be modified.
+Otherwise, the text (or attached files) replaces the challenge, in the same format as `register`.
+
+When the input, the output or the rules change, the existing submissions are evaluated again and
+those which are not valid anymore are removed. An open challenge edited by its author waits for a
+moderator to review it again.
+"##]
+#[usage = "{challenge id}"]
+#[min_args(1)]
+async fn edit(ctx: &Context, msg: &Message) -> CommandResult {
+    let (words, body) = split_command(msg, "edit");
+    let mut args = Args::new(&words.join(" "), &[Delimiter::Single(' ')]);
+    let mut chall = challenge_arg(ctx, msg, &mut args).await?;
+
+    let is_author = match &chall.author {
+        Some(author) => author.id == msg.author.id.0,
+        None => false,
+    };
+    let is_mod = is_moderator(ctx, msg).await;
+
+    if !is_author && !is_mod {
+        msg.reply(
+            ctx,
+            "Only the author of this challenge or a moderator can edit it.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if body.trim().is_empty() && msg.attachments.is_empty() {
+        return send_template(
+            ctx,
+            msg,
+            &chall,
+            format!(
+                "Send `?edit {}` followed by the modified challenge, or with this file attached.",
+                chall.name()
+            ),
+        )
+        .await;
+    }
+
+    let draft = read_draft(ctx, msg, body).await?;
+
+    if let Some(slug) = draft.setting("name") {
+        let slug = slug.to_ascii_lowercase();
+
+        if chall.slug.as_ref() != Some(&slug) {
+            if !check_slug(ctx, msg, &slug).await? {
+                return Ok(());
+            }
+            chall.slug = Some(slug);
+        }
+    }
+
+    let previous = chall.clone();
+    apply_draft(&mut chall, draft);
+
+    keep_line_endings(&mut chall.input, &previous.input);
+    keep_line_endings(&mut chall.output, &previous.output);
+
+    for buffer in chall.buffers.iter_mut() {
+        if let Some(old) = previous.buffers.iter().find(|old| old.name == buffer.name) {
+            keep_line_endings(&mut buffer.input, &old.input);
+            keep_line_endings(&mut buffer.output, &old.output);
+        }
+    }
+
+    if report_problems(ctx, msg, &chall, "This challenge can't be edited :").await? {
+        return Ok(());
+    }
+
+    let changed = !chall.evaluates_like(&previous);
+
+    let ticket = if changed && !chall.scores.is_empty() {
+        match worker_ticket(ctx, msg, Some("your edit has not been saved")).await? {
+            Some(ticket) => Some(ticket),
+            None => return Ok(()),
+        }
+    } else {
+        None
+    };
+    let _permit = match &ticket {
+        Some(ticket) => Some(ticket.run().await),
+        None => None,
+    };
+
+    let mut changes = Vec::new();
+    let mut checked: Vec<Submission> = Vec::new();
+
+    loop {
+        // The challenge is read again, so that the submissions accepted during the evaluation
+        // are kept, and evaluated in turn
+        let fresh = Challenge::open(&chall.id)?;
+        chall.scores = fresh.scores;
+        chall.status = fresh.status;
+
+        let unchecked: Vec<Submission> = chall
+            .scores
+            .iter()
+            .filter(|sub| {
+                !checked
+                    .iter()
+                    .any(|other| other.author == sub.author && other.keys == sub.keys)
+            })
+            .cloned()
+            .collect();
+
+        if !changed || unchecked.is_empty() {
+            break;
+        }
+
+        let mut pending = chall.clone();
+        pending.scores = unchecked.clone();
+        changes.extend(engine::revalidate(&pending).await?);
+        checked.extend(unchecked);
+    }
+
+    // Changes by the author are reviewed again before being published
+    let reviewed = !is_mod && chall.is_open();
+    if reviewed {
+        chall.status = Status::Pending;
+    }
+
+    engine::apply(&mut chall, &changes);
+    chall.save()?;
+
+    let was_open = previous.is_open();
+    record(
+        ctx,
+        msg,
+        Action::Edit {
+            challenge: previous,
+        },
+    )
+    .await?;
+
+    let mut builder = MessageBuilder::new();
+    builder
+        .push("Succesfully edited ")
+        .push_mono(&chall.id)
+        .push_line(".");
+
+    if reviewed {
+        builder.push_line("It will be published again once a moderator reviewed it.");
+    }
+
+    if !changes.is_empty() {
+        builder.push_line("The submissions have been evaluated again :");
+
+        for change in &changes {
+            builder.push("* ").push_line(change.to_string());
+        }
+    }
+
+    msg.reply(ctx, builder.build()).await?;
+
+    if was_open || chall.is_open() {
+        refresh_board(ctx).await?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = r##"Registers a variant of a challenge.
+
+Without a text, the challenge is sent as a file to be modified.
+Otherwise, the text (or attached files) is registered as a new challenge, in the same format as
+`register`, and an optional name can follow the challenge id.
+"##]
+#[usage = "{challenge id} [name]"]
+#[min_args(1)]
+async fn fork(ctx: &Context, msg: &Message) -> CommandResult {
+    let (words, body) = split_command(msg, "fork");
+    let mut args = Args::new(&words.join(" "), &[Delimiter::Single(' ')]);
+    let original = challenge_arg(ctx, msg, &mut args).await?;
+
+    let is_author = match &original.author {
+        Some(author) => author.id == msg.author.id.0,
+        None => false,
+    };
+
+    if !original.is_open() && !is_author {
+        msg.reply(ctx, "Only open challenges can be forked.")
+            .await?;
+        return Ok(());
+    }
+
+    if body.trim().is_empty() && msg.attachments.is_empty() {
+        // The checker and the generator could give away the solution, the variant starts
+        // without them
+        let mut template = original.clone();
+        if !is_author && !is_moderator(ctx, msg).await {
+            template.checker = None;
+            template.generator = None;
+        }
+
+        return send_template(
+            ctx,
+            msg,
+            &template,
+            format!(
+                "Send `?fork {}` followed by your variant, or with this file attached.",
+                original.name()
+            ),
+        )
+        .await;
+    }
+
+    let draft = read_draft(ctx, msg, body).await?;
+
+    let slug = words
+        .get(1)
+        .copied()
+        .or_else(|| draft.setting("name"))
+        .map(|slug| slug.to_ascii_lowercase());
+
+    if let Some(slug) = &slug {
+        if !check_slug(ctx, msg, slug).await? {
+            return Ok(());
+        }
+    }
+
+    let mut chall = new_challenge(msg, draft, slug);
+    chall.forked_from = Some(original.id);
+
+    propose(ctx, msg, chall).await
+}
+
 #[command]
 #[allowed_roles("Conference Admin", "VimGolf mod")]
 #[description = "Closes the provided challenge."]
 #[usage = "{challenge id}"]
 #[num_args(1)]
 async fn close(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if let Ok(mut chall) = args.single::<Challenge>() {
-        std::fs::remove_file(Challenge::filename(&chall.id))?;
+    let mut chall = challenge_arg(ctx, msg, &mut args).await?;
+
+    if !chall.is_open() {
+        msg.reply(ctx, "This challenge is not open.").await?;
+        return Ok(());
+    }
 
-        chall.scores.sort_by(|a, b| a.score.cmp(&b.score));
+    // Closed challenges are kept so that they can be reopened
+    chall.status = Status::Closed;
+    chall.save()?;
 
-        let mut builder = MessageBuilder::new();
+    record(
+        ctx,
+        msg,
+        Action::Close {
+            challenge: chall.clone(),
+        },
+    )
+    .await?;
 
+    chall.scores.sort_by(|a, b| a.score.cmp(&b.score));
+
+    let mut builder = MessageBuilder::new();
+
+    builder
+        .push("Succesfully closed")
+        .push_mono(&chall.id)
+        .push_line("");
+
+    for winner in chall.scores.iter().take(5) {
         builder
-            .push("Succesfully closed")
-            .push_mono(chall.id)
-            .push_line("");
+            .push("* ")
+            .push_bold(&winner.author)
+            .push(" with ")
+            .push_mono(&winner.keys)
+            .push_line(format!(
+                " ({} keys, {})",
+                winner.score,
+                chall.relative_score(winner.score)
+            ));
+    }
 
-        for winner in chall.scores.iter().take(5) {
-            builder
-                .push("* ")
-                .push_bold(&winner.author)
-                .push(" with ")
-                .push_mono(&winner.keys)
-                .push_line(format!(" ({} keys)", winner.score));
+    msg.channel_id.say(ctx, builder.build()).await?;
+
+    refresh_board(ctx).await?;
+
+    Ok(())
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Reopens a closed challenge, along with its submissions."]
+#[usage = "{challenge id}"]
+#[num_args(1)]
+async fn reopen(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut chall = challenge_arg(ctx, msg, &mut args).await?;
+
+    if chall.status != Status::Closed {
+        msg.reply(ctx, "This challenge is not closed.").await?;
+        return Ok(());
+    }
+
+    chall.status = Status::Open;
+    chall.save()?;
+
+    record(
+        ctx,
+        msg,
+        Action::Reopen {
+            challenge: chall.id.clone(),
+        },
+    )
+    .await?;
+
+    msg.reply(ctx, format!("Succesfully reopened `{}`.", chall.id))
+        .await?;
+
+    refresh_board(ctx).await?;
+
+    Ok(())
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = r##"Evaluates the stored submissions again, and reports the ones that changed.
+
+Without a challenge id, the submissions of every challenge are evaluated.
+Scores are only updated, and invalid submissions removed, when `apply` is given.
+"##]
+#[usage = "['apply'] [challenge id]"]
+#[max_args(2)]
+async fn revalidate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let apply = if let Some("apply") = args.current() {
+        args.advance();
+        true
+    } else {
+        false
+    };
+
+    let challenges = if args.is_empty() {
+        Challenge::ids()
+            .into_iter()
+            .filter_map(|id| Challenge::open(&id).ok())
+            .collect()
+    } else {
+        vec![challenge_arg(ctx, msg, &mut args).await?]
+    };
+
+    let ticket = match worker_ticket(ctx, msg, None).await? {
+        Some(ticket) => ticket,
+        None => return Ok(()),
+    };
+
+    msg.reply(
+        ctx,
+        format!(
+            "Evaluating the submissions of {} challenges, this may take a while.",
+            challenges.len()
+        ),
+    )
+    .await?;
+
+    let mut report = Vec::new();
+    // Whether some changes could not be found or applied
+    let mut incomplete = false;
+
+    {
+        let _permit = ticket.run().await;
+
+        for chall in challenges {
+            let changes = match engine::revalidate(&chall).await {
+                Ok(changes) => changes,
+                Err(err) => {
+                    error!("Could not evaluate {} : {}", chall.id, err);
+                    report.push(format!("`{}` could not be evaluated : {}", chall.id, err));
+                    incomplete = true;
+                    continue;
+                }
+            };
+            if changes.is_empty() {
+                continue;
+            }
+
+            report.push(format!("`{}` :", chall.id));
+            for change in &changes {
+                report.push(format!("* {}", change));
+            }
+
+            if apply {
+                match engine::apply_to_stored(&chall, &changes) {
+                    Ok(true) => {
+                        record(
+                            ctx,
+                            msg,
+                            Action::Revalidate {
+                                challenge: chall.id,
+                                changes,
+                            },
+                        )
+                        .await?;
+                    }
+                    Ok(false) => {
+                        report.push(String::from(
+                            "_It has been edited in the meantime, these changes are not applied._",
+                        ));
+                        incomplete = true;
+                    }
+                    Err(err) => {
+                        report.push(format!("_These changes are not applied : {}._", err));
+                        incomplete = true;
+                    }
+                }
+            }
         }
+    }
 
-        msg.channel_id.say(ctx, builder.build()).await?;
+    if report.is_empty() {
+        report.push(String::from(
+            "Every submission is still valid, with the same score.",
+        ));
+    } else if apply && incomplete {
+        report.push(String::from("The other changes have been applied."));
+    } else if apply {
+        report.push(String::from("These changes have been applied."));
     } else {
-        msg.reply(ctx, "Invalid command: invalid challenge id.")
-            .await?;
+        report.push(String::from(
+            "Nothing has been changed yet, use `?revalidate apply` to apply these changes.",
+        ));
     }
 
+    say_lines(&ctx.http, msg.channel_id, &report).await
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = r##"Searches short solutions of a challenge, to tell whether it is too easy.
+
+The search tries sequences of a few common commands, so it only gives an upper bound of the best
+score.
+"##]
+#[usage = "{challenge id}"]
+#[num_args(1)]
+async fn probe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let chall = challenge_arg(ctx, msg, &mut args).await?;
+
+    let ticket = match worker_ticket(ctx, msg, None).await? {
+        Some(ticket) => ticket,
+        None => return Ok(()),
+    };
+
+    msg.reply(ctx, "Searching solutions, this may take a while.")
+        .await?;
+
+    let limits = Limits::default();
+    let search = {
+        let _permit = ticket.run().await;
+        solver::solve(&chall, &limits).await?
+    };
+
+    let mut builder = MessageBuilder::new();
+
+    match search.solution {
+        Some(solution) => builder
+            .push("Found ")
+            .push_mono(&solution.keys)
+            .push(format!(
+                " ({} keys, {})",
+                solution.score,
+                chall.relative_score(solution.score)
+            )),
+        None => builder.push(format!(
+            "No solution found within {} commands",
+            limits.depth
+        )),
+    };
+    builder.push_line(format!(", after trying {} sequences.", search.explored));
+
+    msg.reply(ctx, builder.build()).await?;
+
     Ok(())
 }
 
@@ -144,7 +1018,7 @@ async fn close(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 #[min_args(2)]
 async fn refuse(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mut chall = if args.len() >= 2 {
-        args.single::<Challenge>()?
+        challenge_arg(ctx, msg, &mut args).await?
     } else {
         Challenge::last().ok_or(ArgError::from(String::from("No challenge to open.")))?
     };
@@ -159,9 +1033,168 @@ async fn refuse(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 
         let file = File::create(Challenge::filename(&chall.id))?;
         ron::ser::to_writer(file, &chall)?;
+
+        record(
+            ctx,
+            msg,
+            Action::Refuse {
+                challenge: chall.id,
+                submission: sub,
+            },
+        )
+        .await?;
     } else {
         msg.reply(ctx, "This submission does not exist.").await?;
     }
 
     Ok(())
 }
+
+/// Sends a private message to the author of `chall`, if known.
+async fn notify_author(ctx: &Context, chall: &Challenge, content: String) {
+    if let Some(author) = &chall.author {
+        match UserId(author.id).create_dm_channel(ctx).await {
+            Ok(channel) => {
+                if let Err(why) = channel.say(ctx, content).await {
+                    error!("Could not notify {} : {}", author.name, why);
+                }
+            }
+            Err(why) => error!("Could not open DM with {} : {}", author.name, why),
+        }
+    }
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Lists the challenges waiting for a review."]
+#[usage = ""]
+#[num_args(0)]
+async fn review(ctx: &Context, msg: &Message) -> CommandResult {
+    let pending = Challenge::pending();
+
+    let mut builder = MessageBuilder::new();
+
+    if pending.is_empty() {
+        builder.push_line("No challenge is waiting for a review.");
+    } else {
+        builder.push_line("The challenges waiting for a review are :");
+
+        for chall in pending {
+            builder
+                .push("* ")
+                .push_mono(&chall.id)
+                .push(" : ")
+                .push(&chall.title);
+
+            if let Some(author) = &chall.author {
+                builder.push(" by ").push_bold(&author.name);
+            }
+
+            builder.push_line("");
+        }
+
+        builder.push_line("Use `?describe {challenge id}` to read one of them.");
+    }
+
+    msg.channel_id.say(ctx, builder.build()).await?;
+
+    Ok(())
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Publishes a challenge waiting for a review."]
+#[usage = "{challenge id}"]
+#[num_args(1)]
+async fn approve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut chall = challenge_arg(ctx, msg, &mut args).await?;
+
+    if chall.status != Status::Pending {
+        msg.reply(ctx, "This challenge is not waiting for a review.")
+            .await?;
+        return Ok(());
+    }
+
+    chall.approve(msg.timestamp.timestamp());
+
+    let file = File::create(Challenge::filename(&chall.id))?;
+    ron::ser::to_writer(file, &chall)?;
+
+    record(
+        ctx,
+        msg,
+        Action::Approve {
+            challenge: chall.id.clone(),
+        },
+    )
+    .await?;
+
+    notify_author(
+        ctx,
+        &chall,
+        format!(
+            "Your challenge `{}` ({}) has been approved, it is now open !",
+            chall.id, chall.title
+        ),
+    )
+    .await;
+
+    refresh_board(ctx).await?;
+
+    msg.channel_id
+        .say(
+            ctx,
+            MessageBuilder::new()
+                .push("A new challenge is open : ")
+                .push_mono(&chall.id)
+                .push(" : ")
+                .push_line(&chall.title)
+                .build(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[allowed_roles("Conference Admin", "VimGolf mod")]
+#[description = "Rejects a challenge waiting for a review, the reason is sent to its author."]
+#[usage = "{challenge id} {reason}"]
+#[min_args(2)]
+async fn reject(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let chall = challenge_arg(ctx, msg, &mut args).await?;
+    let reason = args.rest();
+
+    if chall.status != Status::Pending {
+        msg.reply(ctx, "This challenge is not waiting for a review.")
+            .await?;
+        return Ok(());
+    }
+
+    std::fs::remove_file(Challenge::filename(&chall.id))?;
+
+    record(
+        ctx,
+        msg,
+        Action::Reject {
+            challenge: chall.clone(),
+            reason: reason.to_owned(),
+        },
+    )
+    .await?;
+
+    notify_author(
+        ctx,
+        &chall,
+        format!(
+            "Your challenge `{}` ({}) has been rejected : {}",
+            chall.id, chall.title, reason
+        ),
+    )
+    .await;
+
+    msg.reply(ctx, format!("Succesfully rejected `{}`.", chall.id))
+        .await?;
+
+    Ok(())
+}
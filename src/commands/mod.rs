@@ -1,3 +1,108 @@
 pub mod participate;
+pub mod maintenance;
 pub mod manage;
 pub mod reports;
+pub mod audit;
+
+use serenity::framework::standard::{Args, CommandError, CommandResult};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use vim_golf_bot::challenge::{Challenge, ChallengeId, ChallengeError};
+use vim_golf_bot::fuzzy;
+
+/// Maximum length of a discord message.
+pub const MESSAGE_LIMIT: usize = 2000;
+
+/// Sends `lines` in as few messages as possible.
+pub async fn say_lines(http: &Http, channel: ChannelId, lines: &[String]) -> CommandResult {
+    let mut content = String::new();
+
+    for line in lines {
+        if !content.is_empty() && content.chars().count() + line.chars().count() >= MESSAGE_LIMIT {
+            channel.say(http, &content).await?;
+            content.clear();
+        }
+
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    if !content.is_empty() {
+        channel.say(http, &content).await?;
+    }
+
+    Ok(())
+}
+
+/// Roles allowed to run the moderation commands.
+pub const MOD_ROLES: &[&str] = &["Conference Admin", "VimGolf mod"];
+
+/// Whether the author of `msg` has a moderator role in the guild `msg` was sent in.
+pub async fn is_moderator(ctx: &Context, msg: &Message) -> bool {
+    let member = match msg.member(ctx).await {
+        Ok(member) => member,
+        Err(_) => return false,
+    };
+
+    member.roles(ctx).await.map_or(false, |roles| {
+        roles
+            .iter()
+            .any(|role| MOD_ROLES.contains(&role.name.as_str()))
+    })
+}
+
+/// Formats suggestions as "`a`, `b` or `c`".
+pub fn alternatives(suggestions: &[&str]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|s| format!("`{}`", s)).collect();
+
+    match quoted.split_last() {
+        Some((last, [])) => last.to_owned(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// Parses the next argument as a challenge, replying to `msg` when it can't be opened.
+///
+/// Only the challenges the author of `msg` can see are looked up, and close challenge ids are
+/// suggested when the id does not match any of them.
+pub async fn challenge_arg(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> Result<Challenge, CommandError> {
+    let is_mod = is_moderator(ctx, msg).await;
+    let visible = |chall: &Challenge| chall.is_visible_to(msg.author.id.0, is_mod);
+
+    let found = args
+        .single::<String>()?
+        .parse::<ChallengeId>()
+        .and_then(|id| Challenge::find_where(&id, visible));
+
+    match found {
+        Ok(chall) => Ok(chall),
+        Err(err) => {
+            let mut reply = format!("Impossible to open this challenge : {}", err);
+
+            if let ChallengeError::NotFound(id) = &err {
+                let aliases = Challenge::aliases_where(visible);
+                let suggestions = fuzzy::suggest(
+                    id.as_str(),
+                    aliases.iter().map(|(alias, _)| alias.as_str()),
+                );
+
+                if !suggestions.is_empty() {
+                    reply.push_str(&format!(", did you mean {} ?", alternatives(&suggestions)));
+                }
+            } else if let ChallengeError::Io { .. } | ChallengeError::Deserialize { .. } = &err {
+                reply.push_str(", please warn a moderator");
+            }
+
+            msg.reply(ctx, reply).await?;
+
+            Err(CommandError::from(err))
+        }
+    }
+}
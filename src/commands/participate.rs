@@ -1,69 +1,23 @@
-use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandResult};
 use serenity::model::prelude::*;
 use serenity::{prelude::*, utils::MessageBuilder};
 
-use log::info;
+use log::error;
 
-use nvim_rs::{create::tokio as create, rpc::handler::Dummy as DummyHandler};
+use vim_golf_bot::challenge::{Challenge, Status};
+use vim_golf_bot::engine::{emulate, Failure};
 
-use tokio::process::Command;
+use super::challenge_arg;
+use crate::queue::{EvaluationQueue, Refusal};
 
-use std::fs::File;
-use vim_golf_bot::challenge::Challenge;
-
-pub async fn create_nvim_instance(
-) -> nvim_rs::neovim::Neovim<nvim_rs::compat::tokio::Compat<tokio::process::ChildStdin>> {
-    const NVIMPATH: &str = "nvim";
-    let handler = DummyHandler::new();
-
-    let (nvim, _io_handle, _child) = create::new_child_cmd(
-        Command::new(NVIMPATH)
-            .args(&["-u", "NONE", "--embed", "--headless", "-Z", "--noplugin"])
-            .env("NVIM_LOG_FILE", "nvimlog"),
-        handler,
-    )
-    .await
-    .unwrap();
-
-    nvim
-}
-
-async fn emulate(
-    input: &Vec<String>,
-    keys: &str,
-) -> Result<(Vec<String>, usize, Option<String>), CommandError> {
-    let mut nvim = create_nvim_instance().await;
-    let buf = nvim.create_buf(false, true).await?;
-    let win = nvim.get_current_win().await?;
-
-    win.set_buf(&buf).await?;
-    let keys_parsed = nvim.replace_termcodes(keys, true, true, true).await?;
-
-    buf.set_lines(0, -1, false, input.to_owned()).await?;
-
-    info!(
-        "Feeding : {}",
-        keys_parsed.escape_default().collect::<String>()
-    );
-    nvim.feedkeys(&keys_parsed, "ntx", true).await?;
-
-    let err = nvim.get_vvar("errmsg").await.unwrap();
-
-    let out_lines = buf.get_lines(0, -1, false).await?;
-
-    let _ = nvim.quit_no_save().await;
-
-    Ok((
-        out_lines,
-        keys_parsed.len(),
-        err.as_str().map(|s| s.to_owned()),
-    ))
-}
+const QUEUED: char = '⏳';
+const RUNNING: char = '🏃';
 
 #[command]
 #[description = r##"Participate to a challenge.
 This command should be called with two arguments : a challenge ID and keys (as in map rhs)
 Both of the ID and the keys can be escaped within backticks.
+The ID can be shortened to any prefix matching a single challenge.
 When providing a `try` as the first argument, the input will not be submitted.
 This can be used to check your input.
 "##]
@@ -71,7 +25,7 @@ This can be used to check your input.
 #[min_args(1)]
 #[usage("['try'] [challenge id] {key sequence}")]
 pub async fn participate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let (ref mut chall, ref keys, is_try) = match args.len() {
+    let (ref chall, ref keys, is_try) = match args.len() {
         1 => (
             Challenge::last().ok_or(ArgError::from(String::from("No challenge to open.")))?,
             args.single::<String>().unwrap(),
@@ -90,67 +44,241 @@ pub async fn participate(ctx: &Context, msg: &Message, mut args: Args) -> Comman
                 )
             } else {
                 // [challenge id] {seq}
-                (args.single::<Challenge>()?, args.single::<String>()?, false)
+                (challenge_arg(ctx, msg, &mut args).await?, args.single::<String>()?, false)
             }
         }
-        3 => (args.single::<Challenge>()?, args.single::<String>()?, true),
+        3 => (challenge_arg(ctx, msg, &mut args).await?, args.single::<String>()?, true),
 
         _ => unreachable!(),
     };
 
+    match chall.status {
+        Status::Open => {}
+        Status::Pending => {
+            msg.reply(ctx, "This challenge is not open yet.").await?;
+            return Ok(());
+        }
+        Status::Closed => {
+            msg.reply(ctx, "This challenge is closed.").await?;
+            return Ok(());
+        }
+    }
+
     let keys = keys.strip_prefix('`').unwrap_or(keys);
     let keys = keys.strip_suffix('`').unwrap_or(keys);
 
-    let (out_lines, score, err) = emulate(&chall.input.content, &keys).await?;
+    let queue = ctx
+        .data
+        .read()
+        .await
+        .get::<EvaluationQueue>()
+        .cloned()
+        .expect("Evaluation queue is not initialized");
+
+    let ticket = match EvaluationQueue::admit(&queue, msg.author.id) {
+        Ok(ticket) => ticket,
+        Err(Refusal::Busy) => {
+            msg.reply(ctx, "Please wait for your previous submission to be evaluated.")
+                .await?;
+            return Ok(());
+        }
+        Err(Refusal::Cooldown(delay)) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "You are submitting too fast, please retry in {} seconds.",
+                    delay.as_secs() + 1
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
 
-    if chall.output.content.eq(&out_lines) {
+    let position = ticket.position();
+    if position > 0 {
+        msg.react(ctx, QUEUED).await?;
         msg.reply(
             ctx,
-            format!("Your submission is valid ! Your score is : {}", score),
+            format!(
+                "The bot is busy, your submission is queued at position {}.",
+                position
+            ),
         )
         .await?;
+    }
 
-        const DM_CHAN: &str = "DM with";
+    let evaluation = {
+        let _permit = ticket.run().await;
 
-        let channel_name: String = msg
-            .channel_id
-            .name(ctx)
+        if position > 0 {
+            msg.channel_id
+                .delete_reaction(ctx, msg.id, None, QUEUED)
+                .await
+                .ok();
+        }
+        msg.react(ctx, RUNNING).await?;
+
+        let result = emulate(chall, &keys).await;
+
+        msg.channel_id
+            .delete_reaction(ctx, msg.id, None, RUNNING)
             .await
-            .unwrap_or(String::from(DM_CHAN));
+            .ok();
 
-        if !(is_try || channel_name.starts_with(DM_CHAN)) {
-            chall.add_submission(msg.author.name.to_string(), keys.to_owned(), score);
-            let file = File::create(Challenge::filename(&chall.id))?;
-            ron::ser::to_writer(file, &chall)?;
-        }
-    } else {
-        let lang = if let Some(lang) = &chall.output.lang {
-            lang.to_string()
-        } else {
-            String::new()
-        };
-
-        let mut builder = MessageBuilder::new();
+        result?
+    };
+    drop(ticket);
+
+    // A failure on a generated input is explained along with the input
+    let mut builder = MessageBuilder::new();
+    if let Some(case) = &evaluation.case {
         builder
-            .push_underline("Invalid answer")
-            .push(", your result is : ")
-            .push("```")
-            .push_line(lang)
-            .push_line(out_lines.join("\n"))
-            .push_line("```");
-
-        if let Some(err) = err {
-            if !err.is_empty() {
-                builder
-                    .push_line("")
-                    .push_line("An error occurred when executing your input :")
-                    .push_line("```")
-                    .push_line(err)
-                    .push_line("```");
+            .push_line(format!(
+                "Your keys fail on the generated input n°{} :",
+                case.index
+            ))
+            .push_line(case.input.as_markdown())
+            .push_line("Its expected output is :")
+            .push_line(case.output.as_markdown())
+            .push_line("");
+    }
+
+    match &evaluation.failure {
+        Some(Failure::Violations(violations)) => {
+            builder.push_line("Your submission breaks the rules of this challenge :");
+
+            for violation in violations {
+                builder.push("* ").push_line(violation);
+            }
+
+            msg.reply(ctx, builder.build()).await?;
+        }
+        Some(Failure::Unmet(reasons)) => {
+            builder.push_line("Your output is right, but the final state is not :");
+
+            for reason in reasons {
+                builder.push("* ").push_line(reason);
             }
+
+            msg.reply(ctx, builder.build()).await?;
         }
+        Some(Failure::NotIn(required)) => {
+            builder.push(format!(
+                "Invalid answer, this challenge requires keys working in {}",
+                required
+            ));
+
+            match evaluation.works_in {
+                Some(works_in) => builder.push_line(format!(", yours only work in {}.", works_in)),
+                None => builder.push_line(", yours don't work in any editor."),
+            };
 
-        msg.reply(ctx, builder.build()).await?;
+            msg.reply(ctx, builder.build()).await?;
+        }
+        None => {
+            const DM_CHAN: &str = "DM with";
+
+            let channel_name: String = msg
+                .channel_id
+                .name(ctx)
+                .await
+                .unwrap_or(String::from(DM_CHAN));
+
+            if !(is_try || channel_name.starts_with(DM_CHAN)) {
+                // The challenge is read again, since it may have changed while the keys waited
+                // or were evaluated
+                let saved = {
+                    let _lock = Challenge::lock()?;
+                    let mut fresh = Challenge::open(&chall.id)?;
+
+                    let saved = fresh.is_open() && fresh.evaluates_like(chall);
+                    if saved {
+                        fresh.add_submission(
+                            msg.author.name.to_string(),
+                            keys.to_owned(),
+                            evaluation.score,
+                            evaluation.works_in,
+                        );
+                        fresh.save()?;
+                    }
+
+                    saved
+                };
+
+                if !saved {
+                    msg.reply(
+                        ctx,
+                        "This challenge has been closed or edited while your keys were evaluated, \
+                        your submission has not been saved.",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+
+            builder.push(format!(
+                "Your submission is valid ! Your score is : {}",
+                evaluation.score
+            ));
+            if let Some(works_in) = evaluation.works_in {
+                builder.push(format!(", and it works in {}.", works_in));
+            }
+
+            msg.reply(ctx, builder.build()).await?;
+        }
+        Some(Failure::GeneratorError(err)) => {
+            error!("The generator of {} failed : {}", chall.id, err);
+            msg.reply(
+                ctx,
+                "The generator of this challenge failed, please warn a moderator.",
+            )
+            .await?;
+        }
+        Some(failure) => {
+            builder
+                .push_underline("Invalid answer")
+                .push(", your result is : ")
+                .push_line(evaluation.output.as_markdown());
+
+            for (name, output) in &evaluation.buffers {
+                builder
+                    .push("Buffer ")
+                    .push_mono(name)
+                    .push_line(" :")
+                    .push_line(output.as_markdown());
+            }
+
+            match failure {
+                Failure::Refused(Some(message)) => {
+                    builder
+                        .push_line("")
+                        .push_bold("The checker says : ")
+                        .push_line(message);
+                }
+                Failure::CheckerError(err) => {
+                    // The error could reveal the checker, only moderators get to see it
+                    error!("The checker of {} failed : {}", chall.id, err);
+                    builder.push_line("").push_line(
+                        "The checker of this challenge failed, please warn a moderator.",
+                    );
+                }
+                _ => {}
+            }
+
+            if let Some(err) = &evaluation.error {
+                if !err.is_empty() {
+                    builder
+                        .push_line("")
+                        .push_line("An error occurred when executing your input :")
+                        .push_line("```")
+                        .push_line(err)
+                        .push_line("```");
+                }
+            }
+
+            msg.reply(ctx, builder.build()).await?;
+        }
     }
 
     Ok(())
@@ -1,33 +1,38 @@
-use serenity::framework::standard::{macros::command, Args, CommandResult, ArgError};
+use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
+use serenity::http::error::Error as HttpError;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 
+use log::{error, info};
+
 use std::fs::File;
-use vim_golf_bot::challenge::Challenge;
+use vim_golf_bot::challenge::{Challenge, Comparison, Layout, TextBlock};
+use vim_golf_bot::config::{Board, Config};
 
-#[command]
-#[description = "Lists the open challenges."]
-#[usage = ""]
-#[num_args(0)]
-async fn list(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut answer = MessageBuilder::new();
-    answer.push_line("The available challenges are :");
+use super::{challenge_arg, MESSAGE_LIMIT};
 
-    for pin in msg.channel_id.pins(ctx).await? {
-        msg.channel_id.unpin(ctx, pin).await?;
-    }
+/// The list of open challenges, cut to fit in a message.
+fn board_content() -> String {
+    let mut lines = Vec::new();
 
     for file in Challenge::all() {
         match file {
             Ok(path) => {
                 if let Ok(file) = File::open(path) {
                     if let Ok(chall) = ron::de::from_reader::<_, Challenge>(file) {
-                        answer
-                            .push("* ")
-                            .push_mono(chall.id)
-                            .push(" : ")
-                            .push_line(chall.title);
+                        if !chall.is_open() {
+                            continue;
+                        }
+
+                        lines.push(
+                            MessageBuilder::new()
+                                .push("* ")
+                                .push_mono(chall.name())
+                                .push(" : ")
+                                .push(chall.title)
+                                .build(),
+                        );
                     }
                 }
             }
@@ -35,56 +40,328 @@ async fn list(ctx: &Context, msg: &Message) -> CommandResult {
         }
     }
 
-    let out = msg.channel_id.say(ctx, answer).await?;
-    out.pin(ctx).await?;
+    let mut content = String::from("The available challenges are :\n");
+
+    for (index, line) in lines.iter().enumerate() {
+        let rest = format!("…and {} more.", lines.len() - index);
+
+        // Room is kept for the last line, in case the next lines don't fit
+        let length = content.chars().count() + line.chars().count() + 1;
+        if length + rest.chars().count() > MESSAGE_LIMIT {
+            content.push_str(&rest);
+            break;
+        }
+
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    content
+}
+
+/// Discord error code of a message that does not exist anymore.
+const UNKNOWN_MESSAGE: isize = 10008;
+
+/// Whether `err` means that the message does not exist anymore.
+fn is_unknown_message(err: &SerenityError) -> bool {
+    match err {
+        SerenityError::Http(err) => match &**err {
+            HttpError::UnsuccessfulRequest(response) => response.error.code == UNKNOWN_MESSAGE,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Pins a new board in `channel`, and remembers it as the board.
+async fn pin_board(
+    ctx: &Context,
+    config: &mut Config,
+    channel: ChannelId,
+    content: String,
+) -> CommandResult {
+    let board = channel.say(ctx, content).await?;
+    board.pin(ctx).await?;
+
+    config.board = Some(Board {
+        channel: board.channel_id.0,
+        message: board.id.0,
+    });
+    config.save()?;
 
     Ok(())
 }
 
+/// Updates the pinned board listing the open challenges, returns whether there is one.
+///
+/// If the board was deleted, a new one is pinned in the same channel. Other errors are only
+/// logged, so that the commands changing challenges still succeed.
+pub async fn refresh_board(ctx: &Context) -> Result<bool, CommandError> {
+    let mut config = Config::load();
+    let board = match config.board {
+        Some(board) => board,
+        None => return Ok(false),
+    };
+
+    let content = board_content();
+    let edited = ChannelId(board.channel)
+        .edit_message(ctx, MessageId(board.message), |m| m.content(&content))
+        .await;
+
+    match edited {
+        Ok(_) => {}
+        Err(why) if is_unknown_message(&why) => {
+            info!("The board was deleted, pinning a new one");
+            pin_board(ctx, &mut config, ChannelId(board.channel), content).await?;
+        }
+        Err(why) => error!("Could not edit the board : {}", why),
+    }
+
+    Ok(true)
+}
+
 #[command]
-#[description = "Describes the provided challenge."]
-#[usage = "[challenge id]"]
-#[min_args(0)]
-#[max_args(1)]
-async fn describe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let chall = if args.len() >= 1 {
-        args.single::<Challenge>()
+#[description = "Lists the open challenges.
+
+The first time, the list is pinned in the channel and kept up to date as challenges change."]
+#[usage = ""]
+#[num_args(0)]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    if refresh_board(ctx).await? || msg.is_private() {
+        msg.channel_id.say(ctx, board_content()).await?;
     } else {
-        Challenge::last().ok_or(ArgError::from(String::from("No challenge to open.")))
-    };
+        pin_board(ctx, &mut Config::load(), msg.channel_id, board_content()).await?;
+    }
 
-    if let Ok(chall) = chall {
-        let mut msg_builder = MessageBuilder::new();
+    Ok(())
+}
 
-        msg_builder
-            .push("The ")
-            .push_mono(chall.id)
-            .push_line(" challenge is :");
+fn render_block(block: &TextBlock, show: bool) -> String {
+    if show {
+        block.as_markdown()
+    } else {
+        format!("_{} lines, too long to be shown here._", block.len())
+    }
+}
 
-        msg_builder
-            .push_line("")
-            .push_underline_line(chall.title)
-            .push_line("");
+fn render_with(chall: &Challenge, show_blocks: bool) -> String {
+    let mut msg_builder = MessageBuilder::new();
 
-        msg_builder
-            .push_line(chall.description)
-            .push_line("");
+    msg_builder.push("The ").push_mono(&chall.id);
+
+    if let Some(slug) = &chall.slug {
+        msg_builder.push(" (or ").push_mono(slug).push(")");
+    }
+
+    msg_builder.push_line(" challenge is :");
+
+    msg_builder
+        .push_line("")
+        .push_underline_line(&chall.title);
+
+    if let Some(author) = &chall.author {
+        msg_builder.push("Proposed by ").push_bold_line(&author.name);
+    }
+
+    if let Some(original) = &chall.forked_from {
+        msg_builder.push("Variant of ").push_mono_line(original);
+    }
+
+    msg_builder.push_line("");
+
+    msg_builder
+        .push_line(&chall.description)
+        .push_line("");
+
+    msg_builder.push_bold_line("Input:");
 
-        msg_builder.push_bold_line("Input:");
+    msg_builder
+        .push_line(render_block(&chall.input, show_blocks))
+        .push_line("");
 
+    msg_builder.push_bold_line("Output :");
+    msg_builder.push_line(render_block(&chall.output, show_blocks));
+
+    if !chall.comparison.is_empty() {
+        msg_builder.push_italic_line(format!(
+            "The output is compared {}.",
+            chall
+                .comparison
+                .iter()
+                .map(Comparison::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    if chall.checker.is_some() {
+        msg_builder.push_italic_line(
+            "Other outputs can be accepted by the checker of this challenge, this one is an example.",
+        );
+    }
+
+    msg_builder.push_line("");
+
+    if let Some(file) = &chall.file {
         msg_builder
-            .push_line(chall.input.as_markdown())
+            .push("The input is in the buffer ")
+            .push_mono(file)
+            .push_line(".")
             .push_line("");
+    }
 
-        msg_builder.push_bold_line("Output :");
+    for buffer in &chall.buffers {
+        msg_builder
+            .push_bold("Buffer ")
+            .push_mono(&buffer.name)
+            .push_line(" :");
         msg_builder
-            .push_line(chall.output.as_markdown())
+            .push_line("Input :")
+            .push_line(render_block(&buffer.input, show_blocks))
+            .push_line("Output :")
+            .push_line(render_block(&buffer.output, show_blocks))
             .push_line("");
+    }
+
+    if !chall.buffers.is_empty() {
+        msg_builder.push_italic_line(match chall.layout {
+            Layout::Hidden => "The other buffers are hidden, they can be reached with `:bnext`.",
+            Layout::Split => "The other buffers are shown in windows above the input.",
+            Layout::Vsplit => "The other buffers are shown in windows left of the input.",
+            Layout::Tabs => "The other buffers are shown in tab pages after the input.",
+        });
+        msg_builder.push_line("");
+    }
+
+    if let Some(generator) = &chall.generator {
+        msg_builder.push_italic_line(format!(
+            "Submissions also have to solve {} generated inputs.",
+            generator.cases
+        ));
+        msg_builder.push_line("");
+    }
+
+    if !chall.runtime.is_empty() {
+        let parts = chall
+            .runtime
+            .iter()
+            .map(|runtime| runtime.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        msg_builder.push_italic_line(
+            match chall.filetype.as_deref().or_else(|| chall.input.filetype()) {
+                Some(filetype) => format!(
+                    "nvim runs with its {} files for the `{}` filetype.",
+                    parts, filetype
+                ),
+                None => format!("nvim runs with its {} files.", parts),
+            },
+        );
+        msg_builder.push_line("");
+    }
 
-        msg.channel_id.say(ctx, msg_builder.build()).await?;
+    if let Some(editor) = chall.editor {
+        msg_builder.push_italic_line(format!("Submissions have to work in {}.", editor));
+        msg_builder.push_line("");
+    }
+
+    if !chall.constraints.is_empty() {
+        msg_builder.push_bold_line("Rules :");
+
+        for constraint in &chall.constraints {
+            msg_builder.push("* ").push_line(constraint.to_string());
+        }
+
+        msg_builder.push_line("");
+    }
+
+    if !chall.postconditions.is_empty() {
+        msg_builder.push_bold_line("Final state :");
+
+        for postcondition in &chall.postconditions {
+            msg_builder.push("* ").push_line(postcondition.to_string());
+        }
+
+        msg_builder.push_line("");
+    }
+
+    msg_builder.push_bold("Scores : ");
+    if let Some(par) = chall.par {
+        msg_builder.push(format!("par {} keys, ", par));
+    }
+    msg_builder
+        .push_line(format!(
+            "the naive solution takes {} keys.",
+            chall.baseline()
+        ))
+        .push_line("");
+
+    msg_builder.build()
+}
+
+/// Appends the hints of `chall` to `content`, as long as they fit in a message.
+fn push_hints(content: &mut String, chall: &Challenge) {
+    let more = |count| format!("_…and {} more hints._", count);
+    let fits = |content: &String, line: &str, count| {
+        content.chars().count() + line.chars().count() + more(count).chars().count()
+            <= MESSAGE_LIMIT
+    };
+
+    let title = MessageBuilder::new().push_bold_line("Hints :").build();
+    if chall.hints.is_empty() || !fits(content, &title, chall.hints.len()) {
+        return;
+    }
+    content.push_str(&title);
+
+    for (index, hint) in chall.hints.iter().enumerate() {
+        let line = MessageBuilder::new()
+            .push("* ")
+            .push_spoiler_line(hint)
+            .build();
+
+        // Room is kept for the last line, in case the next hints don't fit
+        let rest = chall.hints.len() - index;
+        if !fits(content, &line, rest) {
+            content.push_str(&more(rest));
+            break;
+        }
+
+        content.push_str(&line);
+    }
+}
+
+/// Renders a challenge as shown by `describe`.
+///
+/// The input and output are left out when they don't fit in a message, then the hints that
+/// still don't fit.
+pub fn render_challenge(chall: &Challenge) -> String {
+    let mut content = render_with(chall, true);
+
+    if content.chars().count() > MESSAGE_LIMIT {
+        content = render_with(chall, false);
+    }
+
+    push_hints(&mut content, chall);
+    content
+}
+
+#[command]
+#[description = "Describes the provided challenge."]
+#[usage = "[challenge id]"]
+#[min_args(0)]
+#[max_args(1)]
+async fn describe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let chall = if args.len() >= 1 {
+        challenge_arg(ctx, msg, &mut args).await?
+    } else if let Some(chall) = Challenge::last() {
+        chall
     } else {
         msg.reply(ctx, "Impossible to open this challenge.").await?;
-    }
+        return Ok(());
+    };
+
+    msg.channel_id.say(ctx, render_challenge(&chall)).await?;
 
     Ok(())
 }
@@ -112,7 +389,16 @@ async fn submissions(ctx: &Context, msg: &Message, mut args: Args) -> CommandRes
                     .push_bold(&sub.author)
                     .push(" with : ")
                     .push_mono(&sub.keys)
-                    .push_line(format!(" ({} pts).", sub.score));
+                    .push(format!(
+                        " ({} pts, {}",
+                        sub.score,
+                        chall.relative_score(sub.score)
+                    ));
+
+                if let Some(works_in) = sub.works_in {
+                    builder.push(format!(", works in {}", works_in));
+                }
+                builder.push_line(").");
             }
         } else {
             builder
@@ -129,9 +415,11 @@ async fn submissions(ctx: &Context, msg: &Message, mut args: Args) -> CommandRes
             describe(&mut chall, &mut builder);
         }
     } else {
-        for mut chall in args.iter::<Challenge>().filter_map(|c| c.ok()) {
-            describe(&mut chall, &mut builder);
-            builder.push_line("");
+        while !args.is_empty() {
+            if let Ok(mut chall) = challenge_arg(ctx, msg, &mut args).await {
+                describe(&mut chall, &mut builder);
+                builder.push_line("");
+            }
         }
     }
 
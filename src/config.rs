@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+
+/// The pinned message listing the open challenges.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Board {
+    pub channel: u64,
+    pub message: u64,
+}
+
+/// Bot settings that moderators can change at runtime.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Channel where moderator actions are mirrored.
+    #[serde(default)]
+    pub mod_log: Option<u64>,
+    #[serde(default)]
+    pub board: Option<Board>,
+}
+
+impl Config {
+    pub const FILE: &'static str = "config.ron";
+
+    /// Loads the configuration, falling back to the defaults when there is none.
+    pub fn load() -> Self {
+        File::open(Self::FILE)
+            .ok()
+            .and_then(|file| ron::de::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> ron::Result<()> {
+        let file = File::create(Self::FILE)?;
+        ron::ser::to_writer(file, self)
+    }
+}
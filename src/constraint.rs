@@ -0,0 +1,637 @@
+//! Rules restricting how a challenge can be solved.
+//!
+//! Some rules are checked on the keys before running them, the others by observing the editor
+//! while the keys run.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// Editor modes that can be forbidden.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Insert,
+    Replace,
+    Visual,
+    /// Typing an ex command after `:`.
+    Cmdline,
+    /// Typing a search pattern after `/` or `?`.
+    Search,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Insert,
+        Mode::Replace,
+        Mode::Visual,
+        Mode::Cmdline,
+        Mode::Search,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Insert => "insert",
+            Mode::Replace => "replace",
+            Mode::Visual => "visual",
+            Mode::Cmdline => "cmdline",
+            Mode::Search => "search",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Splits keys written as in a mapping in the keys they stand for.
+///
+/// Special keys like `<Esc>` or `<C-a>` are a single key, written in lowercase.
+pub fn tokenize(keys: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut rest = keys;
+
+    while let Some(c) = rest.chars().next() {
+        let special = if c == '<' {
+            rest.find('>').map(|end| &rest[..=end]).filter(|token| {
+                token.len() > 2
+                    && token[1..token.len() - 1]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '[')
+            })
+        } else {
+            None
+        };
+
+        match special {
+            Some(token) => {
+                rest = &rest[token.len()..];
+
+                tokens.push(match token.to_ascii_lowercase().as_str() {
+                    "<enter>" | "<return>" => String::from("<cr>"),
+                    "<c-[>" => String::from("<esc>"),
+                    other => other.to_owned(),
+                });
+            }
+            None => {
+                tokens.push(c.to_string());
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Whether `pattern` appears in `tokens`.
+fn contains(tokens: &[String], pattern: &[String]) -> bool {
+    !pattern.is_empty()
+        && tokens
+            .windows(pattern.len())
+            .any(|window| window == pattern)
+}
+
+/// Whether `tokens` may enter visual or select mode, judging from the keys alone.
+///
+/// Only used when nvim can't tell when visual mode is entered, since text typed in insert mode
+/// also matches.
+pub fn has_visual_keys(tokens: &[String]) -> bool {
+    tokens
+        .iter()
+        .any(|token| matches!(token.as_str(), "v" | "V" | "<c-v>" | "<c-q>"))
+        || contains(tokens, &tokenize("gv"))
+        || contains(tokens, &tokenize("gh"))
+        || contains(tokens, &tokenize("gH"))
+}
+
+/// Ex commands, with the characters that can be left out in brackets as in `:help`.
+const COMMANDS: &str = "a[ppend] ab[breviate] argdo b[uffer] bd[elete] bn[ext] bp[revious] bufdo \
+    c[hange] cal[l] cdo ce[nter] cfdo co[py] d[elete] delm[arks] di[splay] dig[raphs] e[dit] \
+    ec[ho] echoe[rr] echom[sg] echon el[se] elsei[f] en[dif] endf[unction] endfo[r] endw[hile] ene[w] ev[al] ex exe[cute] f[ile] filt[er] \
+    fo[ld] for fu[nction] g[lobal] go[to] h[elp] i[nsert] if j[oin] k ke[epmarks] keepj[umps] \
+    keepp[atterns] l[ist] le[ft] let lua luado luaf[ile] m[ove] ma[rk] map marks new nm[ap] nn[oremap] \
+    no[remap] noh[lsearch] norm[al] nu[mber] o[pen] p[rint] pu[t] py[thon] py3 pyd[o] q[uit] \
+    r[ead] red[o] reg[isters] res[ize] ret[ab] ri[ght] s[ubstitute] sil[ent] sl[eep] sm[agic] \
+    sno[magic] so[urce] sor[t] sp[lit] star[tinsert] startr[eplace] stopi[nsert] t tabdo u[ndo] \
+    undoj[oin] unl[et] v[global] vert[ical] vi[sual] vne[w] vs[plit] w[rite] wh[ile] wi[nsize] windo wq \
+    x[it] y[ank] z & && ~ < > ! = @ * #";
+
+/// Commands running the commands given as their argument, or evaluating Vim script or Lua that
+/// can run commands, which are not observed.
+const NESTING: &[&str] = &[
+    "=",
+    "argdo",
+    "bufdo",
+    "call",
+    "cdo",
+    "cfdo",
+    "echo",
+    "echoerr",
+    "echomsg",
+    "echon",
+    "elseif",
+    "eval",
+    "execute",
+    "filter",
+    "for",
+    "function",
+    "global",
+    "if",
+    "keepjumps",
+    "keepmarks",
+    "keeppatterns",
+    "let",
+    "lua",
+    "luado",
+    "luafile",
+    "normal",
+    "silent",
+    "source",
+    "tabdo",
+    "vertical",
+    "vglobal",
+    "while",
+    "windo",
+];
+
+/// Functions running ex commands or Lua code, which can be called from expressions like the
+/// replacement of `:s/a/\=execute('d')/`.
+const EVALUATING: &[&str] = &[
+    "execute(",
+    "luaeval(",
+    "nvim_cmd(",
+    "nvim_command(",
+    "nvim_exec",
+    "v:lua.",
+    "vim.cmd",
+];
+
+/// Whether `tokens` use the expression register, with `<C-r>=` in insert or cmdline mode, or with
+/// `"=` and `@=` in normal mode.
+///
+/// Text typed in insert mode also matches, since nvim can't tell when the register is evaluated.
+pub fn has_expression_keys(tokens: &[String]) -> bool {
+    tokens
+        .windows(2)
+        .any(|pair| matches!(pair[0].as_str(), "<c-r>" | "\"" | "@") && pair[1] == "=")
+        || tokens.windows(3).any(|keys| {
+            keys[0] == "<c-r>"
+                && matches!(keys[1].as_str(), "<c-r>" | "<c-o>" | "<c-p>")
+                && keys[2] == "="
+        })
+}
+
+/// The full name of the ex command abbreviated as `name`, if it is a known one.
+pub fn full_command(name: &str) -> Option<String> {
+    COMMANDS.split_whitespace().find_map(|command| {
+        let short = command.find('[').unwrap_or(command.len());
+        let full = command.replace(['[', ']'], "");
+
+        (name.len() >= short && full.starts_with(name)).then_some(full)
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Constraint {
+    /// None of these key sequences can be typed.
+    ForbiddenKeys(Vec<String>),
+    /// None of these ex commands, by their full name, can be executed from the command line.
+    ForbiddenCommands(Vec<String>),
+    /// None of these modes can be entered.
+    ForbiddenModes(Vec<Mode>),
+    /// The keys are only ex commands, each typed after `:` and ended by `<CR>`.
+    ExOnly,
+}
+
+impl Constraint {
+    /// Reads the constraint declared by the setting `key`, `None` if it does not declare one.
+    pub fn from_setting(key: &str, value: &str) -> Option<Result<Self, String>> {
+        let words = || {
+            value
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        let constraint = match key {
+            "forbid" => Ok(Constraint::ForbiddenKeys(words())),
+            // Commands are stored by their full name, as they are compared once the keys ran
+            "forbid-commands" => value
+                .split_whitespace()
+                .map(|command| {
+                    let name = command.trim_start_matches(':');
+                    full_command(name)
+                        .ok_or_else(|| format!("`:{}` is not a known ex command", name))
+                })
+                .collect::<Result<_, _>>()
+                .map(Constraint::ForbiddenCommands),
+            "forbid-modes" => value
+                .split_whitespace()
+                .map(|name| {
+                    Mode::from_name(name).ok_or_else(|| {
+                        format!(
+                            "`{}` is not a mode, expected one of : {}",
+                            name,
+                            Mode::ALL
+                                .iter()
+                                .map(|mode| mode.name())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()
+                .map(Constraint::ForbiddenModes),
+            "ex-only" => match value {
+                "true" | "yes" => Ok(Constraint::ExOnly),
+                _ => Err(String::from("expected `true`")),
+            },
+            _ => return None,
+        };
+
+        Some(constraint.and_then(|constraint| match &constraint {
+            Constraint::ForbiddenKeys(list) | Constraint::ForbiddenCommands(list)
+                if list.is_empty() =>
+            {
+                Err(String::from("expected a space separated list"))
+            }
+            Constraint::ForbiddenModes(modes) if modes.is_empty() => {
+                Err(String::from("expected a space separated list"))
+            }
+            _ => Ok(constraint),
+        }))
+    }
+
+    /// The setting declaring this constraint, as read by `from_setting`.
+    pub fn to_setting(&self) -> (String, String) {
+        let (key, value) = match self {
+            Constraint::ForbiddenKeys(keys) => ("forbid", keys.join(" ")),
+            Constraint::ForbiddenCommands(commands) => ("forbid-commands", commands.join(" ")),
+            Constraint::ForbiddenModes(modes) => (
+                "forbid-modes",
+                modes
+                    .iter()
+                    .map(|mode| mode.name())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Constraint::ExOnly => ("ex-only", String::from("true")),
+        };
+
+        (key.to_owned(), value)
+    }
+
+    /// Whether the constraint is checked while the keys run, see `check_session`.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Constraint::ForbiddenCommands(_) | Constraint::ForbiddenModes(_) => true,
+            Constraint::ForbiddenKeys(_) | Constraint::ExOnly => false,
+        }
+    }
+
+    /// Checks the keys before running them, returns why they break the constraint.
+    pub fn check_keys(&self, tokens: &[String]) -> Option<String> {
+        match self {
+            Constraint::ForbiddenKeys(forbidden) => forbidden
+                .iter()
+                .find(|keys| contains(tokens, &tokenize(keys)))
+                .map(|keys| format!("`{}` can't be used", keys)),
+            Constraint::ExOnly => {
+                let mut in_command = false;
+
+                for token in tokens {
+                    match (in_command, token.as_str()) {
+                        (false, ":") => in_command = true,
+                        (false, _) => {
+                            return Some(format!(
+                                "only ex commands can be used, but `{}` is typed outside of one",
+                                token
+                            ))
+                        }
+                        (true, "<cr>") => in_command = false,
+                        (true, _) => {}
+                    }
+                }
+
+                if in_command {
+                    Some(String::from("the last ex command is not ended by `<CR>`"))
+                } else {
+                    None
+                }
+            }
+            // What expressions run is not observed, like the arguments of nesting commands
+            Constraint::ForbiddenCommands(_) => {
+                let keys = tokens.concat();
+
+                if let Some(function) = EVALUATING.iter().find(|function| keys.contains(*function))
+                {
+                    Some(format!(
+                        "`{}` can't be used, since some ex commands are forbidden",
+                        function.trim_end_matches(['(', '.'])
+                    ))
+                } else if has_expression_keys(tokens) {
+                    Some(String::from(
+                        "the expression register can't be used, since some ex commands are \
+                        forbidden",
+                    ))
+                } else {
+                    None
+                }
+            }
+            Constraint::ForbiddenModes(_) => None,
+        }
+    }
+
+    /// Checks what happened while the keys ran, returns why it breaks the constraint.
+    ///
+    /// `commands` are the full names of the executed ex commands.
+    pub fn check_session(&self, modes: &[Mode], commands: &[String]) -> Option<String> {
+        match self {
+            Constraint::ForbiddenModes(forbidden) => modes
+                .iter()
+                .find(|mode| forbidden.contains(mode))
+                .map(|mode| format!("the {} mode can't be entered", mode.name())),
+            Constraint::ForbiddenCommands(forbidden) => commands.iter().find_map(|command| {
+                if forbidden.contains(command) {
+                    Some(format!("`:{}` can't be executed", command))
+                } else if NESTING.contains(&command.as_str()) {
+                    // The commands they run could be forbidden ones
+                    Some(format!(
+                        "`:{}` can't be executed, since some ex commands are forbidden",
+                        command
+                    ))
+                } else {
+                    None
+                }
+            }),
+            Constraint::ForbiddenKeys(_) | Constraint::ExOnly => None,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::ForbiddenKeys(keys) => {
+                write!(f, "forbidden keys : `{}`", keys.join("`, `"))
+            }
+            Constraint::ForbiddenCommands(commands) => {
+                write!(f, "forbidden ex commands : `:{}`", commands.join("`, `:"))
+            }
+            Constraint::ForbiddenModes(modes) => write!(
+                f,
+                "forbidden modes : {}",
+                modes
+                    .iter()
+                    .map(|mode| mode.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Constraint::ExOnly => write!(f, "only ex commands"),
+        }
+    }
+}
+
+/// The names of the ex commands run by `cmdline`, separated by `|`.
+///
+/// The arguments of the commands running other commands, like `:g`, are not split, since they
+/// are refused when some commands are forbidden.
+pub fn command_names(cmdline: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = cmdline;
+
+    loop {
+        let name = command_name(rest);
+        names.push(name);
+
+        if full_command(name).is_some_and(|full| NESTING.contains(&full.as_str())) {
+            break;
+        }
+
+        // A `|` escaped by a backslash is part of the arguments, like in `:s/a\|b/c/`
+        let mut escaped = false;
+        let bar = rest.char_indices().find_map(|(index, c)| match c {
+            '|' if !escaped => Some(index),
+            _ => {
+                escaped = c == '\\' && !escaped;
+                None
+            }
+        });
+
+        match bar {
+            Some(index) => rest = &rest[index + 1..],
+            None => break,
+        }
+    }
+
+    names
+}
+
+/// The name of the ex command run by `cmdline`, skipping its range.
+pub fn command_name(cmdline: &str) -> &str {
+    let mut rest = cmdline.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+
+    loop {
+        let mut chars = rest.chars();
+
+        rest = match chars.next() {
+            // Patterns of the range, like in `:/foo/d`
+            Some(delimiter @ '/') | Some(delimiter @ '?') => match chars.as_str().find(delimiter) {
+                Some(end) => &chars.as_str()[end + 1..],
+                None => "",
+            },
+            // Marks, like in `:'a,'bd`
+            Some('\'') => {
+                chars.next();
+                chars.as_str()
+            }
+            Some(c) if c.is_ascii_digit() || ".,;$%+-\\ \t".contains(c) => chars.as_str(),
+            _ => break,
+        };
+    }
+
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+
+    // Commands like `:s` or `:&` are followed by their arguments without a space
+    match &rest[..end] {
+        "" => &rest[..rest.chars().next().map_or(0, char::len_utf8)],
+        name => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(keys: &str) -> Vec<String> {
+        tokenize(keys)
+    }
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn tokenizes_special_keys() {
+        assert_eq!(tokens("dd<Esc>"), ["d", "d", "<esc>"]);
+        assert_eq!(tokens("i<C-[>"), ["i", "<esc>"]);
+        assert_eq!(tokens(":s<Enter><Return>"), [":", "s", "<cr>", "<cr>"]);
+        assert_eq!(tokens("<C-v>j"), ["<c-v>", "j"]);
+        // Not a key notation, the characters are typed
+        assert_eq!(tokens("a<b c>"), ["a", "<", "b", " ", "c", ">"]);
+        assert_eq!(tokens("<>"), ["<", ">"]);
+    }
+
+    #[test]
+    fn finds_command_names() {
+        assert_eq!(command_name("s/a/b/"), "s");
+        assert_eq!(command_name(":%norm A;"), "norm");
+        assert_eq!(command_name("/foo/d"), "d");
+        assert_eq!(command_name("?foo?,/bar/m0"), "m");
+        assert_eq!(command_name("'a,'bs/x/y/"), "s");
+        assert_eq!(command_name(".,$-2y"), "y");
+        assert_eq!(command_name("&&"), "&");
+        assert_eq!(command_name(""), "");
+    }
+
+    #[test]
+    fn splits_commands_on_bars() {
+        assert_eq!(command_names("s/a/b/|d"), ["s", "d"]);
+        assert_eq!(command_names("s/a\\|b/c/"), ["s"]);
+        assert_eq!(command_names("g/a/s//b/|d"), ["g"]);
+        assert_eq!(command_names("%norm A|x"), ["norm"]);
+    }
+
+    #[test]
+    fn expands_abbreviations() {
+        assert_eq!(full_command("s").as_deref(), Some("substitute"));
+        assert_eq!(full_command("norm").as_deref(), Some("normal"));
+        assert_eq!(full_command("no").as_deref(), Some("noremap"));
+        assert_eq!(full_command("exe").as_deref(), Some("execute"));
+        assert_eq!(full_command("&").as_deref(), Some("&"));
+        assert_eq!(full_command("nor").as_deref(), Some("noremap"));
+        assert_eq!(full_command("substitutes"), None);
+        assert_eq!(full_command("ex").as_deref(), Some("ex"));
+    }
+
+    #[test]
+    fn normalizes_forbidden_commands() {
+        assert_eq!(
+            Constraint::from_setting("forbid-commands", ":s norm"),
+            Some(Ok(Constraint::ForbiddenCommands(strings(&[
+                "substitute",
+                "normal"
+            ]))))
+        );
+        assert!(matches!(
+            Constraint::from_setting("forbid-commands", "frobnicate"),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn checks_keys() {
+        let forbid = Constraint::ForbiddenKeys(strings(&["dd", "<Esc>"]));
+        assert!(forbid.check_keys(&tokens("d2d")).is_none());
+        assert!(forbid.check_keys(&tokens("ddp")).is_some());
+        assert!(forbid.check_keys(&tokens("ia<C-[>")).is_some());
+
+        let ex_only = Constraint::ExOnly;
+        assert!(ex_only.check_keys(&tokens(":'a,'bs/x/y/<CR>")).is_none());
+        assert!(ex_only.check_keys(&tokens(":d<CR>x")).is_some());
+        assert!(ex_only.check_keys(&tokens(":d")).is_some());
+    }
+
+    #[test]
+    fn checks_sessions() {
+        let commands = Constraint::from_setting("forbid-commands", "s")
+            .unwrap()
+            .unwrap();
+        assert!(commands
+            .check_session(&[], &strings(&["delete", "move"]))
+            .is_none());
+        assert!(commands
+            .check_session(&[], &strings(&["substitute"]))
+            .is_some());
+        assert!(commands.check_session(&[], &strings(&["global"])).is_some());
+        // `:sort` starts like `:s`, but is another command
+        assert!(commands.check_session(&[], &strings(&["sort"])).is_none());
+
+        let modes = Constraint::ForbiddenModes(vec![Mode::Insert]);
+        assert!(modes.check_session(&[Mode::Cmdline], &[]).is_none());
+        assert!(modes.check_session(&[Mode::Insert], &[]).is_some());
+    }
+
+    /// The full names of the commands run by `cmdline`, as the engine observes them.
+    fn executed(cmdline: &str) -> Vec<String> {
+        command_names(cmdline)
+            .into_iter()
+            .map(|name| full_command(name).unwrap_or_else(|| name.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn refuses_commands_running_forbidden_ones() {
+        let commands = Constraint::from_setting("forbid-commands", "s")
+            .unwrap()
+            .unwrap();
+
+        for cmdline in [
+            "call execute('%s/a/b/')",
+            "echo execute('%s/a/b/')",
+            "let _ = execute('%s/a/b/')",
+            "lua vim.cmd('%s/a/b/')",
+            "= vim.cmd('%s/a/b/')",
+            "luado vim.cmd('s/a/b/')",
+            "luafile script.lua",
+            "so script.vim",
+            "d|cal execute('%s/a/b/')",
+        ] {
+            assert!(
+                commands.check_session(&[], &executed(cmdline)).is_some(),
+                "{}",
+                cmdline
+            );
+        }
+
+        assert!(commands.check_session(&[], &executed("d|m0")).is_none());
+    }
+
+    #[test]
+    fn refuses_expressions_when_commands_are_forbidden() {
+        let commands = Constraint::from_setting("forbid-commands", "s")
+            .unwrap()
+            .unwrap();
+
+        assert!(commands
+            .check_keys(&tokens("i<C-r>=execute('%s/a/b/')<CR><Esc>"))
+            .is_some());
+        assert!(commands
+            .check_keys(&tokens("i<C-r>=5*5<CR><Esc>"))
+            .is_some());
+        assert!(commands.check_keys(&tokens("\"=5*5<CR>p")).is_some());
+        assert!(commands.check_keys(&tokens("@=\"dd\"<CR>")).is_some());
+        assert!(commands
+            .check_keys(&tokens(":g/x/s//\\=execute('d')/<CR>"))
+            .is_some());
+        assert!(commands
+            .check_keys(&tokens(":nnoremap <expr> x nvim_exec2('s/a/b/', {})<CR>x"))
+            .is_some());
+        assert!(commands.check_keys(&tokens("ia=b<Esc>dd")).is_none());
+
+        let modes = Constraint::ForbiddenModes(vec![Mode::Insert]);
+        assert!(modes.check_keys(&tokens("i<C-r>=5<CR>")).is_none());
+    }
+
+    #[test]
+    fn guesses_visual_keys() {
+        assert!(has_visual_keys(&tokens("Vd")));
+        assert!(has_visual_keys(&tokens("<C-v>jd")));
+        assert!(has_visual_keys(&tokens("gvd")));
+        assert!(!has_visual_keys(&tokens("dd")));
+    }
+}
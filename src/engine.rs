@@ -0,0 +1,839 @@
+//! Evaluation of key sequences in an embedded nvim instance.
+
+use serde::{Deserialize, Serialize};
+
+use log::info;
+
+use nvim_rs::{
+    compat::tokio::Compat, create::tokio as create, error::CallError,
+    rpc::handler::Dummy as DummyHandler, Neovim, Value,
+};
+
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::time::timeout;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::challenge::{
+    self, Challenge, ChallengeError, Editor, FileFormat, Generator, Submission, TextBlock,
+};
+use crate::constraint::{command_names, full_command, has_visual_keys, tokenize, Constraint, Mode};
+use crate::postcondition::{mode_name, FinalState};
+use crate::vim;
+
+pub type Nvim = Neovim<Compat<ChildStdin>>;
+type Buffer = nvim_rs::Buffer<Compat<ChildStdin>>;
+type Window = nvim_rs::Window<Compat<ChildStdin>>;
+
+/// Why keys could not be evaluated, as opposed to why they don't solve a challenge.
+#[derive(Debug)]
+pub enum EngineError {
+    /// The editor could not be started.
+    Spawn(io::Error),
+    /// Vim could not be run.
+    Vim(io::Error),
+    /// nvim did not answer a request as expected.
+    Nvim(Box<CallError>),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Spawn(err) => write!(f, "the editor could not be started ({})", err),
+            EngineError::Vim(err) => write!(f, "Vim could not be run ({})", err),
+            EngineError::Nvim(err) => write!(f, "nvim failed ({})", err),
+        }
+    }
+}
+
+impl Error for EngineError {}
+
+impl From<Box<CallError>> for EngineError {
+    fn from(err: Box<CallError>) -> Self {
+        EngineError::Nvim(err)
+    }
+}
+
+/// An embedded nvim, killed when dropped so that errors don't leave it running.
+pub struct Instance {
+    nvim: Nvim,
+    _child: Child,
+}
+
+impl Deref for Instance {
+    type Target = Nvim;
+
+    fn deref(&self) -> &Nvim {
+        &self.nvim
+    }
+}
+
+pub async fn create_nvim_instance() -> Result<Instance, EngineError> {
+    const NVIMPATH: &str = "nvim";
+    let handler = DummyHandler::new();
+
+    let (nvim, _io_handle, child) = create::new_child_cmd(
+        Command::new(NVIMPATH)
+            .args(["-u", "NONE", "--embed", "--headless", "-Z", "--noplugin"])
+            .env("NVIM_LOG_FILE", "nvimlog")
+            .kill_on_drop(true),
+        handler,
+    )
+    .await
+    .map_err(EngineError::Spawn)?;
+
+    Ok(Instance {
+        nvim,
+        _child: child,
+    })
+}
+
+/// Why keys do not solve a challenge.
+pub enum Failure {
+    /// The keys break constraints of the challenge.
+    Violations(Vec<String>),
+    /// The output of a buffer is not the expected one.
+    WrongOutput,
+    /// The checker of the challenge refused the output, with an optional explanation.
+    Refused(Option<String>),
+    /// The checker of the challenge raised an error.
+    CheckerError(String),
+    /// The final state of the editor does not meet the post-conditions of the challenge.
+    Unmet(Vec<String>),
+    /// The generator of the challenge raised an error.
+    GeneratorError(String),
+    /// The keys don't work in the editors required by the challenge.
+    NotIn(Editor),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Violations(violations) => {
+                write!(f, "the rules are broken : {}", violations.join(", "))
+            }
+            Failure::WrongOutput => write!(f, "the output is not the expected one"),
+            Failure::Refused(Some(message)) => write!(f, "the output is refused : {}", message),
+            Failure::Refused(None) => write!(f, "the output is refused"),
+            Failure::CheckerError(err) => write!(f, "the checker failed : {}", err),
+            Failure::Unmet(reasons) => write!(f, "{}", reasons.join(", ")),
+            Failure::GeneratorError(err) => write!(f, "the generator failed : {}", err),
+            Failure::NotIn(editor) => write!(f, "the keys don't work in {}", editor),
+        }
+    }
+}
+
+/// An input made by the generator of a challenge, along with its expected output.
+pub struct Case {
+    /// Position of the input among the generated ones, starting at 1.
+    pub index: usize,
+    pub input: TextBlock,
+    pub output: TextBlock,
+}
+
+/// The result of feeding keys to the input of a challenge.
+pub struct Evaluation {
+    pub output: TextBlock,
+    /// The outputs of the other buffers, along with their names.
+    pub buffers: Vec<(String, TextBlock)>,
+    pub score: usize,
+    /// The last error message.
+    pub error: Option<String>,
+    /// Why the keys do not solve the challenge, if they don't.
+    pub failure: Option<Failure>,
+    pub state: FinalState,
+    /// The generated input the keys were fed to, if they failed on one.
+    pub case: Option<Case>,
+    /// The editors the keys work in.
+    pub works_in: Option<Editor>,
+}
+
+/// Autocommands recording what the keys do, for the dynamic constraints.
+const OBSERVERS: &[&str] = &[
+    "let g:golf_modes = [] | let g:golf_cmdlines = []",
+    "autocmd InsertEnter * call add(g:golf_modes, v:insertmode ==# 'r' ? 'replace' : 'insert')",
+    "autocmd CmdlineEnter * call add(g:golf_modes, \
+        expand('<afile>') ==# ':' ? 'cmdline' : expand('<afile>') =~# '[/?]' ? 'search' : '')",
+    "autocmd CmdlineLeave : if !v:event.abort | call add(g:golf_cmdlines, getcmdline()) | endif",
+    // Only recent versions of nvim can tell when visual mode is entered
+    "if exists('##ModeChanged') \
+        | execute \"autocmd ModeChanged *:[vV\\x16]* call add(g:golf_modes, 'visual')\" \
+        | endif",
+];
+
+fn strings(value: Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .filter_map(|value| value.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Checks the dynamic constraints against what the keys did, once they ran.
+///
+/// When nvim can't tell when visual mode is entered, it is guessed from the keys `tokens`.
+async fn check_session(
+    nvim: &Nvim,
+    constraints: &[Constraint],
+    tokens: &[String],
+) -> Result<Vec<String>, Box<CallError>> {
+    let mut modes: Vec<Mode> = strings(nvim.get_var("golf_modes").await?)
+        .iter()
+        .filter_map(|name| Mode::from_name(name))
+        .collect();
+
+    let has_modechanged = nvim.eval("exists('##ModeChanged')").await?.as_i64() == Some(1);
+    if !has_modechanged && has_visual_keys(tokens) {
+        modes.push(Mode::Visual);
+    }
+
+    let has_fullcommand = nvim.eval("exists('*fullcommand')").await?.as_i64() == Some(1);
+
+    let mut commands = Vec::new();
+    for cmdline in strings(nvim.get_var("golf_cmdlines").await?) {
+        for name in command_names(&cmdline) {
+            let full_name = if has_fullcommand && !name.is_empty() {
+                nvim.call_function("fullcommand", vec![Value::from(name)])
+                    .await?
+                    .as_str()
+                    .filter(|full_name| !full_name.is_empty())
+                    .map(String::from)
+            } else {
+                None
+            };
+
+            commands.push(
+                full_name
+                    .or_else(|| full_command(name))
+                    .unwrap_or_else(|| name.to_owned()),
+            );
+        }
+    }
+
+    Ok(constraints
+        .iter()
+        .filter_map(|constraint| constraint.check_session(&modes, &commands))
+        .collect())
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(other, _)| other.as_str() == Some(key))
+        .map(|(_, value)| value)
+}
+
+/// How long the Lua code of a challenge can run, the instance is killed past it.
+const LUA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Defines `sandbox(code, name, globals)`, which loads `code` as a function running in an
+/// environment of its own, without access to the system or to nvim besides a few pure helpers.
+const SANDBOX: &str = r#"
+local function copy(source)
+    local copied = {}
+    for key, value in pairs(source) do copied[key] = value end
+    return copied
+end
+
+local function sandbox(code, name, globals)
+    local chunk, err = loadstring(code, '=' .. name)
+    if not chunk then return nil, err end
+
+    local env = {
+        assert = assert, error = error, ipairs = ipairs, next = next, pairs = pairs,
+        pcall = pcall, select = select, tonumber = tonumber, tostring = tostring, type = type,
+        unpack = unpack, math = copy(math), string = copy(string), table = copy(table),
+        vim = {
+            deepcopy = vim.deepcopy, endswith = vim.endswith, inspect = vim.inspect,
+            split = vim.split, startswith = vim.startswith, tbl_contains = vim.tbl_contains,
+            tbl_filter = vim.tbl_filter, tbl_keys = vim.tbl_keys, tbl_map = vim.tbl_map,
+            tbl_values = vim.tbl_values, trim = vim.trim, json = vim.json and copy(vim.json),
+        },
+    }
+    for key, value in pairs(globals) do env[key] = value end
+
+    return setfenv(chunk, env)
+end
+"#;
+
+/// Runs the checker given as the first argument, with the globals `lines`, `input` and `buffers`.
+const CHECKER: &str = r#"
+local code, lines, input, buffers = ...
+local checker, err = sandbox(code, 'checker', { lines = lines, input = input, buffers = buffers })
+if not checker then return { error = err } end
+local ran, valid, message = pcall(checker)
+if not ran then return { error = tostring(valid) } end
+return { valid = valid == true, message = message }
+"#;
+
+/// Runs the Lua `checker` on the output `lines`, in the instance the keys ran in.
+///
+/// The outputs of the other `buffers` are given by name. The instance can't be used anymore once
+/// the checker timed out.
+async fn check_output(
+    nvim: &Nvim,
+    checker: &str,
+    input: &TextBlock,
+    lines: &[String],
+    buffers: &[(String, TextBlock)],
+) -> Result<Option<Failure>, Box<CallError>> {
+    let to_value = |lines: &[String]| -> Value {
+        Value::from(
+            lines
+                .iter()
+                .map(|line| Value::from(line.as_str()))
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let buffers = Value::Map(
+        buffers
+            .iter()
+            .map(|(name, output)| (Value::from(name.as_str()), to_value(&output.content)))
+            .collect(),
+    );
+
+    let result = timeout(
+        LUA_TIMEOUT,
+        nvim.exec_lua(
+            &format!("{}{}", SANDBOX, CHECKER),
+            vec![
+                Value::from(checker),
+                to_value(lines),
+                to_value(&input.content),
+                buffers,
+            ],
+        ),
+    )
+    .await;
+
+    let result = match result {
+        Ok(result) => result?,
+        Err(_) => {
+            return Ok(Some(Failure::CheckerError(format!(
+                "it did not end within {} seconds",
+                LUA_TIMEOUT.as_secs()
+            ))))
+        }
+    };
+
+    let message = field(&result, "message")
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    Ok(match field(&result, "error").and_then(Value::as_str) {
+        Some(err) => Some(Failure::CheckerError(err.to_owned())),
+        None if field(&result, "valid").and_then(Value::as_bool) == Some(true) => None,
+        None => Some(Failure::Refused(message)),
+    })
+}
+
+/// Captures the cursor, the mode and the registers needed by the post-conditions of `chall`.
+///
+/// The cursor is the one of the current window, which the keys may have changed.
+async fn final_state(nvim: &Nvim, chall: &Challenge) -> Result<FinalState, Box<CallError>> {
+    let (line, column) = nvim.get_current_win().await?.get_cursor().await?;
+
+    let mode = nvim
+        .get_mode()
+        .await?
+        .into_iter()
+        .find(|(key, _)| key.as_str() == Some("mode"))
+        .and_then(|(_, mode)| mode.as_str().map(mode_name))
+        .unwrap_or_default();
+
+    let mut registers = Vec::new();
+    for name in chall.postconditions.iter().filter_map(|p| p.register()) {
+        let lines = strings(
+            nvim.call_function(
+                "getreg",
+                vec![
+                    Value::from(name.to_string()),
+                    Value::from(1),
+                    Value::from(1),
+                ],
+            )
+            .await?,
+        );
+        registers.push((name, lines.join("\n")));
+    }
+
+    Ok(FinalState {
+        cursor: (line as usize, column as usize + 1),
+        mode,
+        registers,
+    })
+}
+
+/// Fills `buf` with the text of `block`.
+///
+/// The `filetype` is only set when some of the runtime is loaded, since it has no effect otherwise.
+async fn load(
+    buf: &Buffer,
+    block: &TextBlock,
+    filetype: Option<&str>,
+) -> Result<(), Box<CallError>> {
+    buf.set_lines(0, -1, false, block.content.to_owned())
+        .await?;
+    buf.set_option("fileformat", Value::from(block.fileformat.option()))
+        .await?;
+    buf.set_option("endofline", Value::from(block.eol)).await?;
+
+    if let Some(filetype) = filetype {
+        buf.set_option("filetype", Value::from(filetype)).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads the text of `buf`, as a block in `lang`.
+async fn read(buf: &Buffer, lang: &Option<String>) -> Result<TextBlock, Box<CallError>> {
+    let content = buf.get_lines(0, -1, false).await?;
+    let fileformat = buf.get_option("fileformat").await?;
+    let eol = buf.get_option("endofline").await?;
+
+    Ok(TextBlock {
+        lang: lang.clone(),
+        content,
+        fileformat: fileformat
+            .as_str()
+            .and_then(FileFormat::from_option)
+            .unwrap_or_default(),
+        eol: eol.as_bool().unwrap_or(true),
+    })
+}
+
+/// Runs the generator given as the first argument once for each input, with the global `index`.
+///
+/// Each input is generated from its own seed, so that changing the number of inputs keeps the
+/// previous ones.
+const GENERATOR: &str = r#"
+local code, seed, cases = ...
+local generated = {}
+for index = 1, cases do
+    math.randomseed(seed + index)
+    local generator, err = sandbox(code, 'generator', { index = index })
+    if not generator then return { error = err } end
+    local ran, input, output = pcall(generator)
+    if not ran then return { error = tostring(input) } end
+    if type(input) ~= 'table' or type(output) ~= 'table' then
+        return { error = 'expected the input and output lines, as two tables' }
+    end
+    generated[index] = { vim.tbl_map(tostring, input), vim.tbl_map(tostring, output) }
+end
+return { cases = generated }
+"#;
+
+/// The lines of generated inputs, along with the lines of their outputs.
+type Cases = Vec<(Vec<String>, Vec<String>)>;
+
+/// Runs `generator`, returns the generated inputs and outputs or the error it raised.
+///
+/// Generators are deterministic, so the inputs are only generated once for each code, seed and
+/// number of inputs.
+async fn generate(generator: &Generator) -> Result<Result<Cases, String>, EngineError> {
+    static GENERATED: Mutex<BTreeMap<(String, u32, usize), Cases>> = Mutex::new(BTreeMap::new());
+
+    let key = (generator.code.clone(), generator.seed, generator.cases);
+    let generated = GENERATED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+        .cloned();
+
+    if let Some(cases) = generated {
+        return Ok(Ok(cases));
+    }
+
+    let nvim = create_nvim_instance().await?;
+    let result = timeout(
+        LUA_TIMEOUT,
+        nvim.exec_lua(
+            &format!("{}{}", SANDBOX, GENERATOR),
+            vec![
+                Value::from(generator.code.as_str()),
+                Value::from(generator.seed),
+                Value::from(generator.cases),
+            ],
+        ),
+    )
+    .await;
+
+    let result = match result {
+        Ok(result) => result?,
+        Err(_) => {
+            return Ok(Err(format!(
+                "it did not end within {} seconds",
+                LUA_TIMEOUT.as_secs()
+            )))
+        }
+    };
+
+    if let Some(err) = field(&result, "error").and_then(Value::as_str) {
+        return Ok(Err(err.to_owned()));
+    }
+
+    let cases: Cases = match field(&result, "cases") {
+        Some(Value::Array(cases)) => cases
+            .iter()
+            .map(|case| match case {
+                Value::Array(pair) if pair.len() == 2 => {
+                    (strings(pair[0].clone()), strings(pair[1].clone()))
+                }
+                _ => (Vec::new(), Vec::new()),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    GENERATED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, cases.clone());
+
+    Ok(Ok(cases))
+}
+
+/// Feeds `keys` to the input of `chall`, and checks whether they solve it.
+///
+/// The keys are also run in Vim when the challenge allows it, and have to work in the editors it
+/// requires.
+pub async fn emulate(chall: &Challenge, keys: &str) -> Result<Evaluation, EngineError> {
+    let mut evaluation = evaluate_cases(chall, keys).await?;
+
+    if let Some(Failure::Violations(_)) = evaluation.failure {
+        return Ok(evaluation);
+    }
+
+    let in_vim = vim::supports(chall) && vim::works(chall, keys).await.map_err(EngineError::Vim)?;
+    evaluation.works_in = Editor::from_results(evaluation.failure.is_none(), in_vim);
+
+    let accepted = match (chall.editor, evaluation.works_in) {
+        (None, works_in) => works_in.is_some(),
+        (Some(required), Some(works_in)) => works_in.satisfies(required),
+        (Some(_), None) => false,
+    };
+
+    if accepted {
+        evaluation.failure = None;
+    } else if let Some(required) = chall.editor {
+        // What went wrong in nvim does not matter when the keys only have to work in Vim
+        if evaluation.failure.is_none() || required == Editor::Vim {
+            evaluation.failure = Some(Failure::NotIn(required));
+        }
+    }
+
+    Ok(evaluation)
+}
+
+/// Evaluates `keys` in nvim, on the input of `chall` and then on every generated input.
+async fn evaluate_cases(chall: &Challenge, keys: &str) -> Result<Evaluation, EngineError> {
+    let evaluation = evaluate(chall, keys).await?;
+
+    let generator = match &chall.generator {
+        Some(generator) if evaluation.failure.is_none() => generator,
+        _ => return Ok(evaluation),
+    };
+
+    let cases = match generate(generator).await? {
+        Ok(cases) => cases,
+        Err(err) => {
+            return Ok(Evaluation {
+                failure: Some(Failure::GeneratorError(err)),
+                ..evaluation
+            })
+        }
+    };
+
+    for (index, (input, output)) in cases.into_iter().enumerate() {
+        let mut case = chall.clone();
+        case.input = chall.input.with_content(input);
+        case.output = chall.output.with_content(output);
+        case.generator = None;
+
+        let mut other = evaluate(&case, keys).await?;
+
+        if other.failure.is_some() {
+            other.case = Some(Case {
+                index: index + 1,
+                input: case.input,
+                output: case.output,
+            });
+            return Ok(other);
+        }
+    }
+
+    Ok(evaluation)
+}
+
+/// The buffers of a challenge once loaded, along with the window of the input.
+pub(crate) struct Prepared<'a> {
+    pub buf: Buffer,
+    pub win: Window,
+    /// The other buffers, along with the ones of the challenge they hold.
+    pub others: Vec<(&'a challenge::Buffer, Buffer)>,
+}
+
+/// Loads the runtime, the input and the other buffers of `chall` in `nvim`, as the keys find
+/// them.
+pub(crate) async fn prepare<'a>(
+    nvim: &Nvim,
+    chall: &'a Challenge,
+) -> Result<Prepared<'a>, Box<CallError>> {
+    let input = &chall.input;
+
+    let uses_runtime = !chall.runtime.is_empty();
+    if uses_runtime {
+        // Only the runtime bundled with nvim is used, so that results don't depend on the host
+        nvim.command("set runtimepath=$VIMRUNTIME packpath=")
+            .await?;
+        for runtime in &chall.runtime {
+            nvim.command(runtime.command()).await?;
+        }
+    }
+
+    // The other buffers are listed, so that `:bnext` and the like can reach them
+    let listed = !chall.buffers.is_empty();
+    let buf = nvim.create_buf(listed, true).await?;
+    let win = nvim.get_current_win().await?;
+
+    win.set_buf(&buf).await?;
+
+    load(
+        &buf,
+        input,
+        chall
+            .filetype
+            .as_deref()
+            .or_else(|| input.filetype())
+            .filter(|_| uses_runtime),
+    )
+    .await?;
+    if let Some(file) = &chall.file {
+        buf.set_name(file).await?;
+    }
+
+    let mut others = Vec::new();
+    for other in &chall.buffers {
+        let other_buf = nvim.create_buf(true, true).await?;
+        let filetype = other.input.filetype().filter(|_| uses_runtime);
+        load(&other_buf, &other.input, filetype).await?;
+        other_buf.set_name(&other.name).await?;
+
+        if let Some(command) = chall.layout.command() {
+            nvim.command(command).await?;
+            nvim.get_current_win().await?.set_buf(&other_buf).await?;
+            nvim.set_current_win(&win).await?;
+        }
+
+        others.push((other, other_buf));
+    }
+
+    Ok(Prepared { buf, win, others })
+}
+
+/// Feeds `keys` to the input of `chall`, and checks whether they solve it.
+async fn evaluate(chall: &Challenge, keys: &str) -> Result<Evaluation, EngineError> {
+    let input = &chall.input;
+    let constraints = &chall.constraints;
+
+    let tokens = tokenize(keys);
+    let mut violations: Vec<String> = constraints
+        .iter()
+        .filter_map(|constraint| constraint.check_keys(&tokens))
+        .collect();
+
+    let nvim = create_nvim_instance().await?;
+
+    let Prepared { buf, others, .. } = prepare(&nvim, chall).await?;
+    let keys_parsed = nvim.replace_termcodes(keys, true, true, true).await?;
+
+    let observe = constraints.iter().any(Constraint::is_dynamic);
+    if observe {
+        for command in OBSERVERS {
+            nvim.command(command).await?;
+        }
+    }
+
+    info!(
+        "Feeding : {}",
+        keys_parsed.escape_default().collect::<String>()
+    );
+    // Insert mode is ended once the keys ran, unless the final mode matters
+    let flags = if chall.postconditions.is_empty() {
+        "ntx"
+    } else {
+        "ntx!"
+    };
+    nvim.feedkeys(&keys_parsed, flags, true).await?;
+
+    let err = nvim.get_vvar("errmsg").await?;
+
+    let output = read(&buf, &input.lang).await?;
+
+    let mut outputs = Vec::new();
+    for (other, other_buf) in &others {
+        outputs.push((
+            other.name.clone(),
+            read(other_buf, &other.input.lang).await?,
+        ));
+    }
+
+    let state = final_state(&nvim, chall).await?;
+
+    if observe {
+        violations.extend(check_session(&nvim, constraints, &tokens).await?);
+    }
+
+    let failure = if !violations.is_empty() {
+        Some(Failure::Violations(violations))
+    } else if let Some(checker) = &chall.checker {
+        check_output(&nvim, checker, input, &output.content, &outputs).await?
+    } else if !chall.output.matches(&output, &chall.comparison)
+        || others
+            .iter()
+            .zip(&outputs)
+            .any(|((other, _), (_, output))| !other.output.matches(output, &chall.comparison))
+    {
+        Some(Failure::WrongOutput)
+    } else {
+        None
+    };
+
+    // The final state only matters once the output is accepted, by the checker or the comparison
+    let failure = failure.or_else(|| {
+        let unmet: Vec<String> = chall
+            .postconditions
+            .iter()
+            .filter_map(|postcondition| postcondition.check(&state))
+            .collect();
+
+        if unmet.is_empty() {
+            None
+        } else {
+            Some(Failure::Unmet(unmet))
+        }
+    });
+
+    Ok(Evaluation {
+        output,
+        buffers: outputs,
+        score: keys_parsed.len(),
+        error: err.as_str().map(|s| s.to_owned()),
+        failure,
+        state,
+        case: None,
+        works_in: None,
+    })
+}
+
+/// What changed for a submission when it was evaluated again.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Change {
+    /// The submission is still valid, but its score changed.
+    Rescored {
+        submission: Submission,
+        score: usize,
+    },
+    /// The submission does not solve the challenge anymore.
+    Invalid { submission: Submission },
+}
+
+impl Change {
+    pub fn submission(&self) -> &Submission {
+        match self {
+            Change::Rescored { submission, .. } | Change::Invalid { submission } => submission,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Rescored { submission, score } => write!(
+                f,
+                "{} with `{}` : {} pts instead of {}",
+                submission.author, submission.keys, score, submission.score
+            ),
+            Change::Invalid { submission } => write!(
+                f,
+                "{} with `{}` : not valid anymore",
+                submission.author, submission.keys
+            ),
+        }
+    }
+}
+
+/// Evaluates every submission of `chall` again, without modifying it.
+///
+/// Only the submissions whose outcome differs are returned, see `apply` to commit them.
+pub async fn revalidate(chall: &Challenge) -> Result<Vec<Change>, EngineError> {
+    let mut changes = Vec::new();
+
+    for sub in &chall.scores {
+        let evaluation = emulate(chall, &sub.keys).await?;
+
+        if evaluation.failure.is_some() {
+            changes.push(Change::Invalid {
+                submission: sub.clone(),
+            });
+        } else if evaluation.score != sub.score {
+            changes.push(Change::Rescored {
+                submission: sub.clone(),
+                score: evaluation.score,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Updates the scores of `chall` and removes its invalid submissions.
+pub fn apply(chall: &mut Challenge, changes: &[Change]) {
+    for change in changes {
+        let sub = change.submission();
+        let index = chall
+            .scores
+            .iter()
+            .position(|other| other.author == sub.author && other.keys == sub.keys);
+
+        match (index, change) {
+            (Some(index), Change::Rescored { score, .. }) => chall.scores[index].score = *score,
+            (Some(index), Change::Invalid { .. }) => {
+                chall.scores.remove(index);
+            }
+            (None, _) => {}
+        }
+    }
+}
+
+/// Applies the `changes` found by `revalidate` on `evaluated` to the stored challenge.
+///
+/// The challenge is read again under the lock, so that what changed during the evaluation, like
+/// new submissions, is kept. Returns whether the changes were applied, they are not when the
+/// challenge has been edited and submissions are not evaluated the same way anymore.
+pub fn apply_to_stored(evaluated: &Challenge, changes: &[Change]) -> Result<bool, ChallengeError> {
+    let _lock = Challenge::lock()?;
+    let mut chall = Challenge::open(&evaluated.id)?;
+
+    if !chall.evaluates_like(evaluated) {
+        return Ok(false);
+    }
+
+    apply(&mut chall, changes);
+    chall.save().map_err(|source| ChallengeError::Serialize {
+        id: chall.id.clone(),
+        source,
+    })?;
+
+    Ok(true)
+}
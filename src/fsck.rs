@@ -0,0 +1,217 @@
+//! Integrity check of the challenges directory.
+//!
+//! Challenges that can't be used are moved to a quarantine directory, so that they can be fixed
+//! by hand instead of silently disappearing from the bot.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::challenge::Challenge;
+
+/// Why a file of the challenges directory can't be used.
+pub enum Problem {
+    Unreadable(io::Error),
+    Invalid(ron::Error),
+    /// The file is not a challenge.
+    Orphaned,
+    /// The id stored in the challenge does not match its file name.
+    Mismatch(String),
+    /// The name is already used by another challenge.
+    Duplicate(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Unreadable(err) => write!(f, "unreadable : {}", err),
+            Problem::Invalid(err) => write!(f, "invalid : {}", err),
+            Problem::Orphaned => write!(f, "not a challenge"),
+            Problem::Mismatch(id) => write!(f, "holds the challenge `{}`", id),
+            Problem::Duplicate(name) => write!(f, "`{}` is already used by a challenge", name),
+        }
+    }
+}
+
+/// A problematic file, and where it was moved.
+pub struct Finding {
+    pub path: PathBuf,
+    pub problem: Problem,
+    pub quarantined: io::Result<PathBuf>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` : {}", self.path.display(), self.problem)?;
+
+        match &self.quarantined {
+            Ok(path) => write!(f, ", moved to `{}`", path.display()),
+            Err(err) => write!(f, ", could not be moved : {}", err),
+        }
+    }
+}
+
+pub const QUARANTINE: &str = "quarantine";
+
+/// Moves `path` to the `quarantine` directory, without overwriting previously quarantined files.
+fn quarantine(path: &Path, quarantine: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(quarantine)?;
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut target = quarantine.join(name.as_ref());
+    let mut attempt = 1;
+
+    while target.exists() {
+        target = quarantine.join(format!("{}.{}", name, attempt));
+        attempt += 1;
+    }
+
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+fn read(path: &Path) -> Result<Challenge, Problem> {
+    if path.extension() != Some(OsStr::new("chal")) {
+        return Err(Problem::Orphaned);
+    }
+
+    let file = fs::File::open(path).map_err(Problem::Unreadable)?;
+    let chall: Challenge = ron::de::from_reader(file).map_err(Problem::Invalid)?;
+
+    match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) if stem == chall.id => Ok(chall),
+        _ => Err(Problem::Mismatch(chall.id)),
+    }
+}
+
+/// Checks every file of the challenges directory, and quarantines the problematic ones.
+pub fn check() -> Vec<Finding> {
+    check_in(Path::new(Challenge::DIR), Path::new(QUARANTINE))
+}
+
+/// Checks the files of `dir`, and moves the problematic ones to `quarantined`.
+///
+/// Only regular files are checked, directories and links are left as they are.
+fn check_in(dir: &Path, quarantined: &Path) -> Vec<Finding> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                entry.file_type().ok()?.is_file().then(|| entry.path())
+            })
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    paths.sort();
+
+    let mut problems = Vec::new();
+    let mut challenges = Vec::new();
+
+    for path in paths {
+        match read(&path) {
+            Ok(chall) => challenges.push((path, chall)),
+            Err(problem) => problems.push((path, problem)),
+        }
+    }
+
+    // Ids are unique since they match the file names, a slug must not clash with any other name
+    let mut names: HashSet<String> = challenges
+        .iter()
+        .map(|(_, chall)| chall.id.to_ascii_lowercase())
+        .collect();
+
+    for (path, chall) in challenges {
+        if let Some(slug) = chall.slug {
+            if !names.insert(slug.to_ascii_lowercase()) {
+                problems.push((path, Problem::Duplicate(slug)));
+            }
+        }
+    }
+
+    // A file holding the same id as another is a duplicate rather than a mismatch
+    for (_, problem) in problems.iter_mut() {
+        if let Problem::Mismatch(id) = problem {
+            if names.contains(&id.to_ascii_lowercase()) {
+                *problem = Problem::Duplicate(id.clone());
+            }
+        }
+    }
+
+    problems
+        .into_iter()
+        .map(|(path, problem)| Finding {
+            quarantined: quarantine(&path, quarantined),
+            path,
+            problem,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::challenge::{Author, TextBlock};
+
+    use std::env;
+    use std::process;
+
+    fn stored(id: &str) -> String {
+        let chall = Challenge::new(
+            String::from("Title"),
+            String::new(),
+            TextBlock::from_text(None, "a\n"),
+            TextBlock::from_text(None, "b\n"),
+            String::from(id),
+            0,
+            Author {
+                id: 0,
+                name: String::from("author"),
+            },
+        );
+        ron::ser::to_string(&chall).unwrap()
+    }
+
+    #[test]
+    fn quarantines_unusable_files() {
+        let root = env::temp_dir().join(format!("vim-golf-bot-fsck-{}", process::id()));
+        let dir = root.join("challenges");
+        let quarantined = root.join("quarantine");
+        fs::create_dir_all(dir.join("archive")).unwrap();
+
+        fs::write(dir.join("abcdef.chal"), stored("abcdef")).unwrap();
+        fs::write(dir.join("bad.chal"), "not a challenge").unwrap();
+        fs::write(dir.join("fedcba.chal"), stored("123456")).unwrap();
+        fs::write(dir.join("copy.chal"), stored("abcdef")).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        // A previously quarantined file is not overwritten
+        fs::create_dir_all(&quarantined).unwrap();
+        fs::write(quarantined.join("notes.txt"), "").unwrap();
+
+        let findings = check_in(&dir, &quarantined);
+        let problem = |name: &str| {
+            &findings
+                .iter()
+                .find(|finding| finding.path.file_name() == Some(OsStr::new(name)))
+                .unwrap_or_else(|| panic!("`{}` should be reported", name))
+                .problem
+        };
+
+        assert_eq!(findings.len(), 4);
+        assert!(matches!(problem("bad.chal"), Problem::Invalid(_)));
+        assert!(matches!(problem("fedcba.chal"), Problem::Mismatch(id) if id == "123456"));
+        assert!(matches!(problem("copy.chal"), Problem::Duplicate(id) if id == "abcdef"));
+        assert!(matches!(problem("notes.txt"), Problem::Orphaned));
+
+        assert!(findings.iter().all(|finding| finding.quarantined.is_ok()));
+        assert!(quarantined.join("bad.chal").is_file());
+        assert!(quarantined.join("notes.txt.1").is_file());
+        assert!(dir.join("abcdef.chal").is_file());
+        assert!(dir.join("archive").is_dir());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+//! Approximate string matching, used to suggest commands and challenge ids.
+
+/// Edit distance between `a` and `b`, counted in characters.
+///
+/// Insertions, deletions, substitutions and transpositions of two adjacent characters all cost
+/// one edit.
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            current[j] = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        before = previous;
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Returns the candidates close enough to `word`, closest first.
+///
+/// The comparison ignores case, and a candidate is close when at most a third of the characters
+/// of `word` need to be changed.
+pub fn suggest<'a, I>(word: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let word = word.to_lowercase();
+    let threshold = (word.chars().count() / 3).max(1);
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (distance(&word, &candidate.to_lowercase()), candidate))
+        .filter(|(dist, _)| *dist <= threshold)
+        .collect();
+
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edits() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("submit", "submit"), 0);
+        assert_eq!(distance("submit", "sumbit"), 1);
+        assert_eq!(distance("submit", "submt"), 1);
+        assert_eq!(distance("submit", "submits"), 1);
+        assert_eq!(distance("submit", "sabmit"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn suggests_close_candidates() {
+        let candidates = ["participate", "leaderboard", "list", "last", "lust", "lost"];
+
+        assert_eq!(suggest("partcipate", candidates), vec!["participate"]);
+        assert_eq!(suggest("LEADERBORD", candidates), vec!["leaderboard"]);
+        assert_eq!(suggest("lst", candidates), vec!["last", "list", "lost"]);
+        assert!(suggest("help", candidates).is_empty());
+        assert_eq!(suggest("list", ["list", "list"]), vec!["list"]);
+    }
+}
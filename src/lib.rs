@@ -1 +1,11 @@
+pub mod audit;
 pub mod challenge;
+pub mod config;
+pub mod constraint;
+pub mod engine;
+pub mod fsck;
+pub mod fuzzy;
+pub mod parser;
+pub mod postcondition;
+pub mod solver;
+pub mod vim;
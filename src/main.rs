@@ -12,10 +12,13 @@
 #[macro_use]
 extern crate serde;
 
+mod cli;
 mod commands;
+mod queue;
 
 use log::{error, info};
 use serenity::{
+    async_trait,
     framework::{
         standard::{
             help_commands,
@@ -28,18 +31,49 @@ use serenity::{
     model::prelude::*,
     prelude::*,
 };
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::iter;
+use std::sync::{Arc, Mutex};
 
-use commands::{manage::*, participate::*, reports::*};
-use vim_golf_bot::challenge::Challenge;
+use commands::{
+    alternatives, audit::*, maintenance::*, manage::*, participate::*, reports::*,
+};
+use queue::EvaluationQueue;
+use vim_golf_bot::{challenge::Challenge, fsck, fuzzy};
 
 struct Handler;
 
-impl EventHandler for Handler {}
+#[async_trait]
+impl EventHandler for Handler {
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        if let Err(why) = confirm_registration(&ctx, &reaction).await {
+            error!("Error confirming a registration : {}", why);
+        }
+    }
+}
 
 #[group]
-#[commands(register, list, describe, participate, close, submissions, refuse)]
+#[commands(
+    register,
+    list,
+    describe,
+    participate,
+    close,
+    submissions,
+    refuse,
+    review,
+    approve,
+    reject,
+    audit,
+    modlog,
+    edit,
+    reopen,
+    fork,
+    revalidate,
+    probe,
+    fsck
+)]
 struct General;
 
 #[help]
@@ -69,6 +103,11 @@ async fn main() {
     // `RUST_LOG` to debug`.
     env_logger::init();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if cli::run(&args).await {
+        return;
+    }
+
     let token = env::args()
         .next_back()
         .expect("Expected a token in the environment");
@@ -82,6 +121,18 @@ async fn main() {
         .map(|info| info.id)
         .ok();
 
+    // Move the challenges that can't be used out of the way
+    let findings = fsck::check();
+    if !findings.is_empty() {
+        for finding in &findings {
+            error!("{}", finding);
+        }
+
+        if let Err(why) = report_to_owner(&http, &findings).await {
+            error!("Could not warn the owner : {}", why);
+        }
+    }
+
     let mut client = Client::new(&token)
         .event_handler(Handler)
         .framework(
@@ -96,6 +147,12 @@ async fn main() {
         .await
         .expect("Err creating client");
 
+    {
+        let mut data = client.data.write().await;
+        data.insert::<EvaluationQueue>(Arc::new(EvaluationQueue::new(EvaluationQueue::WORKERS)));
+        data.insert::<Previews>(Arc::new(Mutex::new(HashMap::new())));
+    }
+
     if let Err(why) = client.start().await {
         error!("Client error: {:?}", why);
     }
@@ -112,8 +169,41 @@ async fn after(_ctx: &Context, _msg: &Message, command_name: &str, error: Comman
 }
 
 #[hook]
-async fn unknown_command(_ctx: &Context, _msg: &Message, unknown_command_name: &str) {
+async fn unknown_command(ctx: &Context, msg: &Message, unknown_command_name: &str) {
     info!("Unknown command : {}", unknown_command_name);
+
+    // Only answer where the bot would have run the command
+    match msg.channel_id.name(ctx).await {
+        Some(name) if name == "vim-golf" || name.starts_with("DM with") => {}
+        _ => return,
+    }
+
+    let names = GENERAL_GROUP
+        .options
+        .commands
+        .iter()
+        .flat_map(|command| command.options.names.iter().copied())
+        .chain(iter::once("help"));
+
+    let suggestions: Vec<String> = fuzzy::suggest(unknown_command_name, names)
+        .into_iter()
+        .map(|name| format!("?{}", name))
+        .collect();
+
+    if !suggestions.is_empty() {
+        let suggestions: Vec<&str> = suggestions.iter().map(String::as_str).collect();
+
+        msg.reply(
+            ctx,
+            format!(
+                "Unknown command `?{}`, did you mean {} ?",
+                unknown_command_name,
+                alternatives(&suggestions)
+            ),
+        )
+        .await
+        .ok();
+    }
 }
 
 #[hook]
@@ -0,0 +1,1170 @@
+//! Parser for the markdown text describing a challenge.
+//!
+//! A challenge starts with a `# Title`, followed by sections. Sections are introduced by a label,
+//! which is either a heading or a paragraph containing only the section name, like `## Input` or
+//! `**Output:**`, possibly followed by the section content on the next lines. Content before the
+//! first label is the description, and code blocks found there are taken as the input then the
+//! output.
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::challenge::{
+    Buffer, Challenge, ChallengeError, Comparison, Editor, Generator, Layout, Runtime, TextBlock,
+};
+use crate::constraint::Constraint;
+use crate::postcondition::Postcondition;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Section {
+    Description,
+    Input,
+    Output,
+    Hints,
+    Settings,
+    Checker,
+    Generator,
+    /// A buffer besides the input, labelled with its name like `## Buffer notes.txt`.
+    Buffer,
+}
+
+impl Section {
+    const ALL: [Section; 7] = [
+        Section::Description,
+        Section::Input,
+        Section::Output,
+        Section::Hints,
+        Section::Settings,
+        Section::Checker,
+        Section::Generator,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Section::Description => "Description",
+            Section::Input => "Input",
+            Section::Output => "Output",
+            Section::Hints => "Hints",
+            Section::Settings => "Settings",
+            Section::Checker => "Checker",
+            Section::Generator => "Generator",
+            Section::Buffer => "Buffer",
+        }
+    }
+
+    /// Recognizes the label of a buffer section, returns the name of the buffer.
+    fn buffer_name(label: &str) -> Option<&str> {
+        let label = label
+            .trim()
+            .trim_matches(|c| c == '*' || c == '_')
+            .trim_end_matches(':')
+            .trim();
+
+        let prefix = Section::Buffer.name();
+        if label.len() <= prefix.len() || !label.is_char_boundary(prefix.len()) {
+            return None;
+        }
+
+        let (start, name) = label.split_at(prefix.len());
+
+        // The name is separated from the label, so that `Buffers` is not a buffer named `s`
+        if !name.starts_with(|c: char| c.is_whitespace() || c == '`') {
+            return None;
+        }
+        let name = name.trim().trim_matches('`');
+
+        if start.eq_ignore_ascii_case(prefix)
+            && !name.is_empty()
+            && !name.contains(char::is_whitespace)
+        {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    /// Recognizes a section label, ignoring case, emphasis and a trailing colon.
+    fn from_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim()
+            .trim_matches(|c| c == '*' || c == '_')
+            .trim_end_matches(':')
+            .trim();
+
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|section| section.name().eq_ignore_ascii_case(label))
+    }
+}
+
+/// A top-level markdown block.
+enum Block<'a> {
+    Heading(u32, &'a str),
+    Paragraph,
+    Code {
+        lang: Option<String>,
+        content: String,
+        closed: bool,
+    },
+    /// The items of a list, with the line they start at.
+    List(Vec<(usize, &'a str)>),
+    Other,
+}
+
+/// A top-level block, along with the line it starts at and its markdown source.
+struct Located<'a> {
+    line: usize,
+    source: &'a str,
+    block: Block<'a>,
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// Text of a heading, without its markers.
+fn heading_text(source: &str) -> &str {
+    let source = source.trim();
+
+    if source.starts_with('#') {
+        source.trim_start_matches('#').trim_end_matches('#').trim()
+    } else {
+        // Setext heading, the last line is the underline
+        let end = source.rfind('\n').unwrap_or(source.len());
+        source[..end].trim()
+    }
+}
+
+/// Text of a list item, without its marker.
+fn item_text(source: &str) -> &str {
+    let source = source.trim();
+    let marker = source.find(char::is_whitespace).unwrap_or(source.len());
+
+    source[marker..].trim()
+}
+
+/// Whether the fenced code block in `source` ends with a fence matching its opening one.
+fn is_closed(source: &str) -> bool {
+    let mut lines = source.trim_end().lines();
+
+    let open = lines.next().unwrap_or("").trim_start();
+    let fence = match open.chars().next() {
+        Some(c) => c,
+        None => return false,
+    };
+    let length = open.chars().take_while(|c| *c == fence).count();
+
+    match lines.last() {
+        Some(close) => {
+            let close = close.trim();
+            close.chars().count() >= length && close.chars().all(|c| c == fence)
+        }
+        None => false,
+    }
+}
+
+fn to_block<'a>(
+    text: &'a str,
+    tag: Tag,
+    range: Range<usize>,
+    code: &str,
+    items: &[Range<usize>],
+) -> Located<'a> {
+    let source = &text[range.clone()];
+
+    let block = match tag {
+        Tag::Heading(level) => Block::Heading(level, heading_text(source)),
+        Tag::Paragraph => Block::Paragraph,
+        Tag::CodeBlock(kind) => {
+            let (lang, closed) = match kind {
+                CodeBlockKind::Fenced(info) => (
+                    info.split_whitespace().next().map(String::from),
+                    is_closed(source),
+                ),
+                CodeBlockKind::Indented => (None, true),
+            };
+
+            Block::Code {
+                lang,
+                content: code.to_owned(),
+                closed,
+            }
+        }
+        Tag::List(_) => Block::List(
+            items
+                .iter()
+                .map(|item| (line_of(text, item.start), item_text(&text[item.clone()])))
+                .collect(),
+        ),
+        _ => Block::Other,
+    };
+
+    Located {
+        line: line_of(text, range.start),
+        source: source.trim(),
+        block,
+    }
+}
+
+/// Splits `text` in top-level blocks.
+fn blocks(text: &str) -> Vec<Located<'_>> {
+    let mut blocks = Vec::new();
+
+    let mut depth = 0;
+    let mut current: Option<(Tag, Range<usize>)> = None;
+    let mut code = String::new();
+    let mut items = Vec::new();
+
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        match event {
+            Event::Start(tag) => {
+                if depth == 0 {
+                    code.clear();
+                    items.clear();
+                    current = Some((tag, range));
+                } else if depth == 1 && tag == Tag::Item {
+                    items.push(range);
+                }
+                depth += 1;
+            }
+            Event::End(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some((tag, range)) = current.take() {
+                        blocks.push(to_block(text, tag, range, &code, &items));
+                    }
+                }
+            }
+            Event::Text(content) if depth == 1 => {
+                if let Some((Tag::CodeBlock(_), _)) = &current {
+                    code.push_str(&content);
+                }
+            }
+            Event::Html(_) | Event::Rule if depth == 0 => blocks.push(Located {
+                line: line_of(text, range.start),
+                source: text[range].trim(),
+                block: Block::Other,
+            }),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Splits a paragraph starting with a label line, like `Settings:` followed by the settings.
+fn split_label(located: Located<'_>) -> Vec<Located<'_>> {
+    if let Block::Paragraph = located.block {
+        if let Some(end) = located.source.find('\n') {
+            let (label, rest) = located.source.split_at(end);
+
+            if Section::from_label(label).is_some() {
+                return vec![
+                    Located {
+                        line: located.line,
+                        source: label,
+                        block: Block::Paragraph,
+                    },
+                    Located {
+                        line: located.line + 1,
+                        source: rest.trim(),
+                        block: Block::Paragraph,
+                    },
+                ];
+            }
+        }
+    }
+
+    vec![located]
+}
+
+/// Splits a setting line of the form `key = value` or `key: value`.
+fn setting(line: usize, text: &str) -> Result<(String, String), ChallengeError> {
+    let invalid = || ChallengeError::InvalidSetting {
+        line,
+        setting: text.to_owned(),
+    };
+
+    let split = text.find(['=', ':']).ok_or_else(invalid)?;
+    let key = text[..split].trim().to_ascii_lowercase();
+    let value = text[split + 1..].trim().to_owned();
+
+    if !Draft::SETTINGS.contains(&key.as_str()) {
+        return Err(invalid());
+    }
+
+    if let Err(reason) = check_value(&key, &value) {
+        return Err(ChallengeError::InvalidValue { line, key, reason });
+    }
+
+    Ok((key, value))
+}
+
+/// Checks the value of the setting `key`, returns why it is not valid.
+fn check_value(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "compare" => return Comparison::from_setting(value).map(|_| ()),
+        "runtime" => return Runtime::from_setting(value).map(|_| ()),
+        "cases" => {
+            return match value.parse::<usize>() {
+                Ok(cases) if cases > 0 && cases <= Generator::MAX_CASES => Ok(()),
+                _ => Err(format!(
+                    "expected a number of inputs between 1 and {}",
+                    Generator::MAX_CASES
+                )),
+            }
+        }
+        "par" => {
+            return match value.parse::<usize>() {
+                Ok(par) if par > 0 => Ok(()),
+                _ => Err(String::from("expected a number of keys")),
+            }
+        }
+        "seed" => {
+            return value
+                .parse::<u32>()
+                .map(|_| ())
+                .map_err(|_| String::from("expected a positive number"))
+        }
+        "filetype"
+            if value.is_empty()
+                || !value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') =>
+        {
+            return Err(String::from("expected a filetype, like `rust`"))
+        }
+        "layout" => {
+            return Layout::from_name(value).map(|_| ()).ok_or_else(|| {
+                format!(
+                    "`{}` is not a layout, expected one of : {}",
+                    value,
+                    Layout::ALL
+                        .iter()
+                        .map(|layout| layout.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+        }
+        "editor" => {
+            return Editor::from_name(value).map(|_| ()).ok_or_else(|| {
+                format!(
+                    "`{}` is not an editor, expected one of : {}",
+                    value,
+                    Editor::ALL
+                        .iter()
+                        .map(|editor| editor.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+        }
+        "file" if value.is_empty() || value.contains(char::is_whitespace) => {
+            return Err(String::from("expected a buffer name, without spaces"))
+        }
+        _ => {}
+    }
+
+    match Constraint::from_setting(key, value)
+        .map(|constraint| constraint.map(|_| ()))
+        .or_else(|| Postcondition::from_setting(key, value).map(|p| p.map(|_| ())))
+    {
+        Some(Err(reason)) => Err(reason),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlBlock {
+    lang: Option<String>,
+    content: String,
+}
+
+impl From<TomlBlock> for TextBlock {
+    fn from(block: TomlBlock) -> Self {
+        TextBlock::from_text(block.lang, &block.content)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlBuffer {
+    name: String,
+    input: TomlBlock,
+    output: TomlBlock,
+}
+
+impl From<TomlBuffer> for Buffer {
+    fn from(buffer: TomlBuffer) -> Self {
+        Buffer {
+            name: buffer.name,
+            input: buffer.input.into(),
+            output: buffer.output.into(),
+        }
+    }
+}
+
+/// Layout of a challenge written as a TOML file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlDraft {
+    title: String,
+    #[serde(default)]
+    description: String,
+    input: TomlBlock,
+    output: TomlBlock,
+    #[serde(default)]
+    hints: Vec<String>,
+    #[serde(default)]
+    settings: BTreeMap<String, String>,
+    checker: Option<String>,
+    generator: Option<String>,
+    #[serde(default)]
+    buffers: Vec<TomlBuffer>,
+}
+
+/// A challenge as written by its author, before it is registered.
+pub struct Draft {
+    pub title: String,
+    pub description: String,
+    pub input: TextBlock,
+    pub output: TextBlock,
+    pub hints: Vec<String>,
+    pub settings: Vec<(String, String)>,
+    /// Lua code deciding whether an output is valid, instead of comparing it with the output.
+    pub checker: Option<String>,
+    /// Lua code generating more inputs and outputs, see `generator`.
+    pub generator: Option<String>,
+    pub buffers: Vec<Buffer>,
+}
+
+impl From<&Challenge> for Draft {
+    fn from(chall: &Challenge) -> Self {
+        Draft {
+            title: chall.title.clone(),
+            description: chall.description.clone(),
+            input: chall.input.clone(),
+            output: chall.output.clone(),
+            hints: chall.hints.clone(),
+            // The name is left out, a variant can't take it and an edit keeps it
+            settings: chall
+                .constraints
+                .iter()
+                .map(Constraint::to_setting)
+                .chain(chall.postconditions.iter().map(Postcondition::to_setting))
+                .chain(
+                    chall
+                        .file
+                        .iter()
+                        .map(|file| (String::from("file"), file.clone())),
+                )
+                .chain(if chall.runtime.is_empty() {
+                    None
+                } else {
+                    Some((String::from("runtime"), Runtime::to_setting(&chall.runtime)))
+                })
+                .chain(
+                    chall
+                        .filetype
+                        .iter()
+                        .map(|filetype| (String::from("filetype"), filetype.clone())),
+                )
+                .chain(chall.generator.iter().flat_map(|generator| {
+                    vec![
+                        (String::from("cases"), generator.cases.to_string()),
+                        (String::from("seed"), generator.seed.to_string()),
+                    ]
+                }))
+                .chain(
+                    chall
+                        .par
+                        .iter()
+                        .map(|par| (String::from("par"), par.to_string())),
+                )
+                .chain(if chall.layout == Layout::Hidden {
+                    None
+                } else {
+                    Some((String::from("layout"), chall.layout.name().to_owned()))
+                })
+                .chain(
+                    chall
+                        .editor
+                        .map(|editor| (String::from("editor"), editor.name().to_owned())),
+                )
+                .chain(if chall.comparison.is_empty() {
+                    None
+                } else {
+                    Some((
+                        String::from("compare"),
+                        Comparison::to_setting(&chall.comparison),
+                    ))
+                })
+                .collect(),
+            checker: chall.checker.clone(),
+            generator: chall
+                .generator
+                .as_ref()
+                .map(|generator| generator.code.clone()),
+            buffers: chall.buffers.clone(),
+        }
+    }
+}
+
+impl Draft {
+    /// The keys accepted in the Settings section.
+    pub const SETTINGS: &'static [&'static str] = &[
+        "name",
+        "forbid",
+        "forbid-commands",
+        "forbid-modes",
+        "ex-only",
+        "compare",
+        "cursor",
+        "mode",
+        "register",
+        "file",
+        "layout",
+        "runtime",
+        "filetype",
+        "cases",
+        "seed",
+        "par",
+        "editor",
+    ];
+
+    /// Parses a challenge, line numbers in errors are relative to `text`.
+    pub fn parse(text: &str) -> Result<Self, ChallengeError> {
+        Self::parse_with(text, None, None)
+    }
+
+    /// Parses a challenge whose input or output may be provided separately.
+    pub fn parse_with(
+        text: &str,
+        mut input: Option<TextBlock>,
+        mut output: Option<TextBlock>,
+    ) -> Result<Self, ChallengeError> {
+        let mut blocks = blocks(text).into_iter().flat_map(split_label);
+
+        let title = match blocks.next() {
+            None => return Err(ChallengeError::Empty),
+            Some(Located {
+                block: Block::Heading(1, title),
+                ..
+            }) => title.to_owned(),
+            Some(Located { line, .. }) => return Err(ChallengeError::MissingTitle { line }),
+        };
+
+        let mut section = Section::Description;
+        let mut labelled = Vec::new();
+
+        let mut description = Vec::new();
+        let mut hints = Vec::new();
+        let mut settings = Vec::new();
+        let mut checker = None;
+        let mut generator = None;
+        let mut buffers: Vec<(String, Option<TextBlock>, Option<TextBlock>)> = Vec::new();
+
+        for Located {
+            line,
+            source,
+            block,
+        } in blocks
+        {
+            let label_text = match &block {
+                Block::Heading(_, text) => Some(*text),
+                Block::Paragraph => Some(source),
+                _ => None,
+            };
+
+            if let Some(name) = label_text.and_then(Section::buffer_name) {
+                buffers.push((name.to_owned(), None, None));
+                section = Section::Buffer;
+                continue;
+            }
+
+            let label = label_text.and_then(Section::from_label);
+
+            if let Some(label) = label {
+                let filled = match label {
+                    Section::Input => input.is_some(),
+                    Section::Output => output.is_some(),
+                    _ => false,
+                };
+
+                if filled || labelled.contains(&label) {
+                    return Err(ChallengeError::DuplicateSection {
+                        line,
+                        section: label.name(),
+                    });
+                }
+
+                labelled.push(label);
+                section = label;
+                continue;
+            }
+
+            if let Block::Code { closed: false, .. } = block {
+                return Err(ChallengeError::UnterminatedFence { line });
+            }
+
+            match (section, block) {
+                (Section::Description, Block::Code { lang, content, .. }) => {
+                    // Unlabelled code blocks are the input, then the output
+                    let block = TextBlock::from_text(lang, &content);
+
+                    if input.is_none() {
+                        input = Some(block);
+                    } else if output.is_none() {
+                        output = Some(block);
+                    } else {
+                        return Err(ChallengeError::UnexpectedContent {
+                            line,
+                            section: Section::Output.name(),
+                        });
+                    }
+                }
+                (Section::Description, _) => description.push(source),
+                (Section::Input, Block::Code { lang, content, .. }) if input.is_none() => {
+                    input = Some(TextBlock::from_text(lang, &content));
+                }
+                (Section::Output, Block::Code { lang, content, .. }) if output.is_none() => {
+                    output = Some(TextBlock::from_text(lang, &content));
+                }
+                (Section::Input, _) | (Section::Output, _) => {
+                    return Err(ChallengeError::UnexpectedContent {
+                        line,
+                        section: section.name(),
+                    });
+                }
+                (Section::Hints, Block::List(items)) => {
+                    hints.extend(items.into_iter().map(|(_, item)| item.to_owned()))
+                }
+                (Section::Hints, _) => hints.push(source.to_owned()),
+                (Section::Settings, Block::List(items)) => {
+                    for (line, item) in items {
+                        settings.push(setting(line, item)?);
+                    }
+                }
+                (Section::Settings, Block::Code { content, .. }) => {
+                    // The content starts on the line after the fence
+                    for (index, text) in content.lines().enumerate() {
+                        if !text.trim().is_empty() {
+                            settings.push(setting(line + 1 + index, text)?);
+                        }
+                    }
+                }
+                (Section::Settings, _) => {
+                    for (index, text) in source.lines().enumerate() {
+                        settings.push(setting(line + index, text)?);
+                    }
+                }
+                (Section::Checker, Block::Code { content, .. }) if checker.is_none() => {
+                    checker = Some(content);
+                }
+                (Section::Generator, Block::Code { content, .. }) if generator.is_none() => {
+                    generator = Some(content);
+                }
+                (Section::Checker, _) | (Section::Generator, _) => {
+                    return Err(ChallengeError::UnexpectedContent {
+                        line,
+                        section: section.name(),
+                    });
+                }
+                (Section::Buffer, block) => {
+                    // Buffer sections are only entered once a buffer is pushed
+                    let (name, input, output) = buffers.last_mut().unwrap();
+
+                    match block {
+                        Block::Code { lang, content, .. } if input.is_none() => {
+                            *input = Some(TextBlock::from_text(lang, &content));
+                        }
+                        Block::Code { lang, content, .. } if output.is_none() => {
+                            *output = Some(TextBlock::from_text(lang, &content));
+                        }
+                        _ => return Err(ChallengeError::IncompleteBuffer { name: name.clone() }),
+                    }
+                }
+            }
+        }
+
+        let buffers = buffers
+            .into_iter()
+            .map(|(name, input, output)| match (input, output) {
+                (Some(input), Some(output)) => Ok(Buffer {
+                    name,
+                    input,
+                    output,
+                }),
+                _ => Err(ChallengeError::IncompleteBuffer { name }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Draft {
+            title,
+            description: description.join("\n\n"),
+            input: input.ok_or(ChallengeError::MissingBlock { what: "input" })?,
+            output: output.ok_or(ChallengeError::MissingBlock { what: "output" })?,
+            hints,
+            settings,
+            checker,
+            generator,
+            buffers,
+        })
+    }
+
+    /// Reads a challenge written as a TOML file.
+    pub fn from_toml(text: &str) -> Result<Self, ChallengeError> {
+        let draft: TomlDraft = toml::from_str(text).map_err(ChallengeError::Toml)?;
+
+        for (key, value) in &draft.settings {
+            if !Self::SETTINGS.contains(&key.as_str()) {
+                return Err(ChallengeError::Toml(serde::de::Error::custom(format!(
+                    "unknown setting `{}`, expected one of : {}",
+                    key,
+                    Self::SETTINGS.join(", ")
+                ))));
+            }
+
+            if let Err(reason) = check_value(key, value) {
+                return Err(ChallengeError::Toml(serde::de::Error::custom(format!(
+                    "invalid `{}` setting, {}",
+                    key, reason
+                ))));
+            }
+        }
+
+        Ok(Draft {
+            title: draft.title,
+            description: draft.description,
+            input: draft.input.into(),
+            output: draft.output.into(),
+            hints: draft.hints,
+            settings: draft.settings.into_iter().collect(),
+            checker: draft.checker,
+            generator: draft.generator,
+            buffers: draft.buffers.into_iter().map(Buffer::from).collect(),
+        })
+    }
+
+    /// Whether the attached file `name` holds a whole challenge, rather than a part of it.
+    pub fn is_challenge_file(name: &str) -> bool {
+        let lower = name.to_ascii_lowercase();
+        let (stem, extension) = match lower.rfind('.') {
+            Some(dot) => (&lower[..dot], &lower[dot + 1..]),
+            None => (lower.as_str(), ""),
+        };
+
+        matches!(extension, "md" | "toml") && stem != "input" && stem != "output"
+    }
+
+    /// Reads a challenge from a message `text` and its attached files.
+    ///
+    /// The challenge itself can be attached as a `.md` or `.toml` file, and the input and output
+    /// can be attached as `input.*` and `output.*` files, whose extension gives the language. The
+    /// checker and the generator can be attached as `checker.lua` and `generator.lua` files.
+    pub fn from_attachments(
+        text: &str,
+        files: Vec<(String, Vec<u8>)>,
+    ) -> Result<Self, ChallengeError> {
+        let mut challenge: Option<(String, String)> = None;
+        let mut input: Option<(String, TextBlock)> = None;
+        let mut output: Option<(String, TextBlock)> = None;
+        let mut checker: Option<(String, String)> = None;
+        let mut generator: Option<(String, String)> = None;
+
+        for (name, bytes) in files {
+            let content = match String::from_utf8(bytes) {
+                Ok(content) => content,
+                Err(_) => {
+                    return Err(ChallengeError::InFile {
+                        file: name,
+                        error: Box::new(ChallengeError::NotUtf8),
+                    })
+                }
+            };
+
+            let lower = name.to_ascii_lowercase();
+            let (stem, extension) = match lower.rfind('.') {
+                Some(dot) => (&lower[..dot], &lower[dot + 1..]),
+                None => (lower.as_str(), ""),
+            };
+
+            let lang = match extension {
+                "" | "txt" => None,
+                extension => Some(extension.to_owned()),
+            };
+
+            // The input and output can be markdown too, so they are matched first
+            match (stem, extension) {
+                ("input", _) if input.is_none() => {
+                    input = Some((name, TextBlock::from_text(lang, &content)))
+                }
+                ("output", _) if output.is_none() => {
+                    output = Some((name, TextBlock::from_text(lang, &content)))
+                }
+                _ if Self::is_challenge_file(&name) && challenge.is_none() => {
+                    challenge = Some((name, content))
+                }
+                ("checker", "lua") if checker.is_none() => checker = Some((name, content)),
+                ("generator", "lua") if generator.is_none() => generator = Some((name, content)),
+                _ => return Err(ChallengeError::UnexpectedFile(name)),
+            }
+        }
+
+        let (input, output) = match challenge {
+            Some((ref name, _)) if name.to_ascii_lowercase().ends_with(".toml") => {
+                // A TOML challenge is self-contained
+                if let Some((file, _)) = input.or(output) {
+                    return Err(ChallengeError::UnexpectedFile(file));
+                }
+                if let Some((file, _)) = checker.as_ref().or(generator.as_ref()) {
+                    return Err(ChallengeError::UnexpectedFile(file.clone()));
+                }
+
+                (None, None)
+            }
+            _ => (
+                input.map(|(_, block)| block),
+                output.map(|(_, block)| block),
+            ),
+        };
+
+        let mut draft = match challenge {
+            Some((name, content)) => {
+                let draft = if name.to_ascii_lowercase().ends_with(".toml") {
+                    Self::from_toml(&content)
+                } else {
+                    Self::parse_with(&content, input, output)
+                };
+
+                draft.map_err(|error| ChallengeError::InFile {
+                    file: name,
+                    error: Box::new(error),
+                })?
+            }
+            None => Self::parse_with(text, input, output)?,
+        };
+
+        if let Some((file, code)) = checker {
+            if draft.checker.is_some() {
+                return Err(ChallengeError::UnexpectedFile(file));
+            }
+            draft.checker = Some(code);
+        }
+
+        if let Some((file, code)) = generator {
+            if draft.generator.is_some() {
+                return Err(ChallengeError::UnexpectedFile(file));
+            }
+            draft.generator = Some(code);
+        }
+
+        Ok(draft)
+    }
+
+    /// Writes the draft back in the markdown format read by `parse`.
+    ///
+    /// Line endings and a missing final newline can't be expressed in a code block, they are lost.
+    pub fn to_markdown(&self) -> String {
+        fn push_block(text: &mut String, block: &TextBlock) {
+            // The fence has to be longer than any run of backticks in the content
+            let longest = block
+                .content
+                .iter()
+                .flat_map(|line| line.split(|c| c != '`'))
+                .map(str::len)
+                .max()
+                .unwrap_or(0);
+            let fence = "`".repeat(std::cmp::max(3, longest + 1));
+
+            text.push_str(&fence);
+            if let Some(lang) = &block.lang {
+                text.push_str(lang);
+            }
+            text.push('\n');
+            for line in &block.content {
+                text.push_str(line);
+                text.push('\n');
+            }
+            text.push_str(&fence);
+            text.push_str("\n\n");
+        }
+
+        let mut text = format!("# {}\n\n", self.title);
+
+        if !self.description.is_empty() {
+            text.push_str(&self.description);
+            text.push_str("\n\n");
+        }
+
+        text.push_str("## Input\n");
+        push_block(&mut text, &self.input);
+        text.push_str("## Output\n");
+        push_block(&mut text, &self.output);
+
+        for buffer in &self.buffers {
+            text.push_str(&format!("## Buffer {}\n", buffer.name));
+            push_block(&mut text, &buffer.input);
+            push_block(&mut text, &buffer.output);
+        }
+
+        if !self.hints.is_empty() {
+            text.push_str("## Hints\n");
+            for hint in &self.hints {
+                text.push_str(&format!("- {}\n", hint));
+            }
+            text.push('\n');
+        }
+
+        if !self.settings.is_empty() {
+            text.push_str("## Settings\n");
+            for (key, value) in &self.settings {
+                text.push_str(&format!("- {} = {}\n", key, value));
+            }
+            text.push('\n');
+        }
+
+        if let Some(checker) = &self.checker {
+            text.push_str("## Checker\n");
+            push_block(
+                &mut text,
+                &TextBlock::from_text(Some(String::from("lua")), checker),
+            );
+        }
+
+        if let Some(generator) = &self.generator {
+            text.push_str("## Generator\n");
+            push_block(
+                &mut text,
+                &TextBlock::from_text(Some(String::from("lua")), generator),
+            );
+        }
+
+        text
+    }
+
+    /// The constraints declared in the settings.
+    pub fn constraints(&self) -> Vec<Constraint> {
+        self.settings
+            .iter()
+            .filter_map(|(key, value)| Constraint::from_setting(key, value)?.ok())
+            .collect()
+    }
+
+    /// The post-conditions declared in the settings.
+    pub fn postconditions(&self) -> Vec<Postcondition> {
+        self.settings
+            .iter()
+            .filter_map(|(key, value)| Postcondition::from_setting(key, value)?.ok())
+            .collect()
+    }
+
+    /// The name of the buffer holding the input.
+    pub fn file(&self) -> Option<String> {
+        self.setting("file").map(String::from)
+    }
+
+    /// How the buffers are shown when the keys start.
+    pub fn layout(&self) -> Layout {
+        self.setting("layout")
+            .and_then(Layout::from_name)
+            .unwrap_or_default()
+    }
+
+    /// The editors submissions have to work in, either one by default.
+    pub fn editor(&self) -> Option<Editor> {
+        self.setting("editor").and_then(Editor::from_name)
+    }
+
+    /// The parts of the runtime to load, none by default.
+    pub fn runtime(&self) -> Vec<Runtime> {
+        self.setting("runtime")
+            .and_then(|value| Runtime::from_setting(value).ok())
+            .unwrap_or_default()
+    }
+
+    /// The generator of more inputs, with the number of inputs and the seed from the settings.
+    pub fn to_generator(&self) -> Option<Generator> {
+        Some(Generator {
+            code: self.generator.clone()?,
+            cases: self
+                .setting("cases")
+                .and_then(|cases| cases.parse().ok())
+                .unwrap_or(Generator::DEFAULT_CASES),
+            seed: self
+                .setting("seed")
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The differences tolerated by the comparison of the outputs, none if it is strict.
+    pub fn comparison(&self) -> Vec<Comparison> {
+        self.setting("compare")
+            .and_then(|value| Comparison::from_setting(value).ok())
+            .unwrap_or_default()
+    }
+
+    /// The value of the last occurrence of the setting `key`.
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .rev()
+            .find(|(other, _)| other == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(block: &TextBlock) -> Vec<&str> {
+        block.content.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn parses_labelled_sections() {
+        let draft = Draft::parse(
+            "# Reverse\n\
+             \n\
+             First paragraph.\n\
+             \n\
+             Second paragraph.\n\
+             \n\
+             **Input:**\n\
+             ~~~~text\n\
+             a\n\
+             b\n\
+             ~~~~\n\
+             \n\
+             ## Output\n\
+             ````\n\
+             b\n\
+             a\n\
+             ````\n\
+             \n\
+             Hints:\n\
+             - use `:g`\n\
+             - or `:m0`\n\
+             \n\
+             Settings:\n\
+             par = 8\n",
+        )
+        .unwrap();
+
+        assert_eq!(draft.title, "Reverse");
+        assert_eq!(draft.description, "First paragraph.\n\nSecond paragraph.");
+        assert_eq!(draft.input.lang.as_deref(), Some("text"));
+        assert_eq!(content(&draft.input), ["a", "b"]);
+        assert_eq!(content(&draft.output), ["b", "a"]);
+        assert_eq!(draft.hints, ["use `:g`", "or `:m0`"]);
+        assert_eq!(draft.setting("par"), Some("8"));
+    }
+
+    #[test]
+    fn takes_unlabelled_blocks_as_input_then_output() {
+        let draft = Draft::parse("# Title\n\nSome text.\n```\na\n```\n```\nb\n```\n").unwrap();
+
+        assert_eq!(draft.description, "Some text.");
+        assert_eq!(content(&draft.input), ["a"]);
+        assert_eq!(content(&draft.output), ["b"]);
+    }
+
+    #[test]
+    fn reports_where_the_message_deviates() {
+        assert!(matches!(Draft::parse(""), Err(ChallengeError::Empty)));
+        assert!(matches!(
+            Draft::parse("Text\n\n# Title\n"),
+            Err(ChallengeError::MissingTitle { line: 1 })
+        ));
+        assert!(matches!(
+            Draft::parse("# Title\n\n```\na\n```\n\n```\nb\n"),
+            Err(ChallengeError::UnterminatedFence { line: 7 })
+        ));
+        assert!(matches!(
+            Draft::parse("# Title\n\n## Input\n```\na\n```\n## Input\n"),
+            Err(ChallengeError::DuplicateSection { line: 7, .. })
+        ));
+        assert!(matches!(
+            Draft::parse("# Title\n\n```\na\n```\n"),
+            Err(ChallengeError::MissingBlock { what: "output" })
+        ));
+        assert!(matches!(
+            Draft::parse("# Title\n\n```\na\n```\n```\nb\n```\n## Settings\n- colour = red\n"),
+            Err(ChallengeError::InvalidSetting { line: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn reads_markdown_inputs_from_attachments() {
+        let draft = Draft::from_attachments(
+            "# Title\n",
+            vec![
+                (String::from("input.md"), b"# a\r\n".to_vec()),
+                (String::from("Output.md"), b"b".to_vec()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(draft.input.lang.as_deref(), Some("md"));
+        assert_eq!(content(&draft.input), ["# a"]);
+        assert!(!draft.output.eol);
+        assert!(Draft::is_challenge_file("reverse.MD"));
+        assert!(!Draft::is_challenge_file("input.md"));
+    }
+
+    #[test]
+    fn recognizes_buffer_labels() {
+        assert_eq!(Section::buffer_name("Buffer notes.txt"), Some("notes.txt"));
+        assert_eq!(Section::buffer_name("**buffer `a.rs`:**"), Some("a.rs"));
+        assert_eq!(Section::buffer_name("Buffer`a.rs`"), Some("a.rs"));
+        assert_eq!(Section::buffer_name("Buffers"), None);
+        assert_eq!(Section::buffer_name("Buffer"), None);
+        assert_eq!(Section::buffer_name("Buffer two words"), None);
+    }
+
+    #[test]
+    fn round_trips_through_markdown() {
+        let draft = Draft {
+            title: String::from("Title"),
+            description: String::from("Some `code`.\n\nAnother paragraph."),
+            input: TextBlock::from_text(Some(String::from("rust")), "fn main() {}\n```\n"),
+            output: TextBlock::from_text(None, "\n  indented\n"),
+            hints: vec![String::from("a hint")],
+            settings: vec![
+                (String::from("par"), String::from("3")),
+                (String::from("compare"), String::from("ignore-case")),
+            ],
+            checker: Some(String::from("return true\n")),
+            generator: None,
+            buffers: vec![Buffer {
+                name: String::from("notes.txt"),
+                input: TextBlock::from_text(None, "x\n"),
+                output: TextBlock::from_text(None, "y\n"),
+            }],
+        };
+
+        let parsed = Draft::parse(&draft.to_markdown()).unwrap();
+
+        assert_eq!(parsed.title, draft.title);
+        assert_eq!(parsed.description, draft.description);
+        assert_eq!(parsed.input.lang, draft.input.lang);
+        assert_eq!(parsed.input.content, draft.input.content);
+        assert_eq!(parsed.output.content, draft.output.content);
+        assert_eq!(parsed.hints, draft.hints);
+        assert_eq!(parsed.settings, draft.settings);
+        assert_eq!(parsed.checker, draft.checker);
+        assert_eq!(parsed.generator, None);
+        assert_eq!(parsed.buffers.len(), 1);
+        assert_eq!(parsed.buffers[0].name, "notes.txt");
+        assert_eq!(content(&parsed.buffers[0].input), ["x"]);
+        assert_eq!(content(&parsed.buffers[0].output), ["y"]);
+    }
+}
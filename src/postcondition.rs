@@ -0,0 +1,261 @@
+//! Requirements on the state of the editor once the keys ran, besides the output.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// What the editor looks like once the keys ran.
+pub struct FinalState {
+    /// Line and column of the cursor, both starting at 1, the column being a byte index as in
+    /// `col('.')`.
+    pub cursor: (usize, usize),
+    /// The name of the mode, as in the `mode` setting.
+    pub mode: String,
+    /// The contents of the registers named by the post-conditions, lines joined by `\n`.
+    pub registers: Vec<(char, String)>,
+}
+
+/// Modes the keys can be required to end in.
+pub const MODES: &[&str] = &["normal", "insert", "replace", "visual", "select"];
+
+/// The name of the mode reported by `nvim_get_mode`.
+pub fn mode_name(mode: &str) -> String {
+    match mode.chars().next() {
+        Some('n') => String::from("normal"),
+        Some('i') => String::from("insert"),
+        Some('R') => String::from("replace"),
+        Some('v') | Some('V') | Some('\x16') => String::from("visual"),
+        Some('s') | Some('S') | Some('\x13') => String::from("select"),
+        Some('c') => String::from("cmdline"),
+        _ => mode.to_owned(),
+    }
+}
+
+/// Whether the register `name` can be checked.
+fn is_register(name: char) -> bool {
+    name.is_ascii_lowercase() || name.is_ascii_digit() || "\"-.:/".contains(name)
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Postcondition {
+    /// The cursor ends on this line, and on this column if there is one.
+    Cursor { line: usize, column: Option<usize> },
+    /// The editor ends in this mode.
+    Mode(String),
+    /// The register ends holding this text.
+    Register { name: char, content: String },
+}
+
+impl Postcondition {
+    /// Reads the post-condition declared by the setting `key`, `None` if it does not declare one.
+    pub fn from_setting(key: &str, value: &str) -> Option<Result<Self, String>> {
+        let postcondition = match key {
+            "cursor" => {
+                let numbers = value
+                    .split_whitespace()
+                    .map(|number| number.parse::<usize>().ok().filter(|&number| number > 0))
+                    .collect::<Option<Vec<_>>>();
+
+                match numbers.as_deref() {
+                    Some(&[line]) => Ok(Postcondition::Cursor { line, column: None }),
+                    Some(&[line, column]) => Ok(Postcondition::Cursor {
+                        line,
+                        column: Some(column),
+                    }),
+                    _ => Err(String::from(
+                        "expected a line number, optionally followed by a column number",
+                    )),
+                }
+            }
+            "mode" => {
+                let mode = value.trim().to_ascii_lowercase();
+
+                if MODES.contains(&mode.as_str()) {
+                    Ok(Postcondition::Mode(mode))
+                } else {
+                    Err(format!(
+                        "`{}` is not a mode, expected one of : {}",
+                        value.trim(),
+                        MODES.join(", ")
+                    ))
+                }
+            }
+            "register" => {
+                let mut chars = value.trim_start().chars();
+
+                match chars.next() {
+                    Some(name) if is_register(name.to_ascii_lowercase()) => {
+                        Ok(Postcondition::Register {
+                            name: name.to_ascii_lowercase(),
+                            content: chars.as_str().trim().to_owned(),
+                        })
+                    }
+                    _ => Err(String::from(
+                        "expected a register name, followed by its content",
+                    )),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(postcondition)
+    }
+
+    /// The setting declaring this post-condition, as read by `from_setting`.
+    pub fn to_setting(&self) -> (String, String) {
+        let (key, value) = match self {
+            Postcondition::Cursor { line, column } => (
+                "cursor",
+                match column {
+                    Some(column) => format!("{} {}", line, column),
+                    None => line.to_string(),
+                },
+            ),
+            Postcondition::Mode(mode) => ("mode", mode.clone()),
+            Postcondition::Register { name, content } => {
+                ("register", format!("{} {}", name, content))
+            }
+        };
+
+        (key.to_owned(), value)
+    }
+
+    /// The register this post-condition is about.
+    pub fn register(&self) -> Option<char> {
+        match self {
+            Postcondition::Register { name, .. } => Some(*name),
+            _ => None,
+        }
+    }
+
+    /// Checks the final state of the editor, returns the post-condition along with what happened
+    /// instead when it is not met.
+    pub fn check(&self, state: &FinalState) -> Option<String> {
+        let instead = match self {
+            Postcondition::Cursor { line, column } => {
+                let (actual_line, actual_column) = state.cursor;
+
+                if actual_line == *line && column.is_none_or(|column| column == actual_column) {
+                    return None;
+                }
+                format!(
+                    "the cursor is on line {}, column {}",
+                    actual_line, actual_column
+                )
+            }
+            Postcondition::Mode(mode) => {
+                if *mode == state.mode {
+                    return None;
+                }
+                format!("they end in {} mode", state.mode)
+            }
+            Postcondition::Register { name, content } => {
+                let actual = state
+                    .registers
+                    .iter()
+                    .find(|(other, _)| other == name)
+                    .map_or("", |(_, actual)| actual.as_str());
+
+                if actual == content {
+                    return None;
+                }
+                format!("it holds `{}`", actual)
+            }
+        };
+
+        Some(format!("{}, but {}", self, instead))
+    }
+}
+
+impl fmt::Display for Postcondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Postcondition::Cursor { line, column } => {
+                write!(f, "the cursor ends on line {}", line)?;
+
+                match column {
+                    Some(column) => write!(f, ", column {}", column),
+                    None => Ok(()),
+                }
+            }
+            Postcondition::Mode(mode) => write!(f, "the keys end in {} mode", mode),
+            Postcondition::Register { name, content } => {
+                write!(f, "the register `{}` ends holding `{}`", name, content)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(key: &str, value: &str) -> Option<Result<Postcondition, String>> {
+        Postcondition::from_setting(key, value)
+    }
+
+    #[test]
+    fn reads_cursors() {
+        assert_eq!(
+            parse("cursor", "3"),
+            Some(Ok(Postcondition::Cursor {
+                line: 3,
+                column: None
+            }))
+        );
+        assert_eq!(
+            parse("cursor", " 3  5 "),
+            Some(Ok(Postcondition::Cursor {
+                line: 3,
+                column: Some(5)
+            }))
+        );
+        assert!(matches!(parse("cursor", "0"), Some(Err(_))));
+        assert!(matches!(parse("cursor", "1 2 3"), Some(Err(_))));
+        assert!(matches!(parse("cursor", ""), Some(Err(_))));
+    }
+
+    #[test]
+    fn reads_modes() {
+        assert_eq!(
+            parse("mode", "Insert"),
+            Some(Ok(Postcondition::Mode(String::from("insert"))))
+        );
+        assert!(matches!(parse("mode", "cmdline"), Some(Err(_))));
+    }
+
+    #[test]
+    fn reads_registers() {
+        assert_eq!(
+            parse("register", "A foo bar "),
+            Some(Ok(Postcondition::Register {
+                name: 'a',
+                content: String::from("foo bar")
+            }))
+        );
+        assert_eq!(
+            parse("register", "\""),
+            Some(Ok(Postcondition::Register {
+                name: '"',
+                content: String::new()
+            }))
+        );
+        assert!(matches!(parse("register", "+ foo"), Some(Err(_))));
+        assert!(matches!(parse("register", ""), Some(Err(_))));
+    }
+
+    #[test]
+    fn ignores_other_settings() {
+        assert!(parse("par", "3").is_none());
+    }
+
+    #[test]
+    fn round_trips_through_settings() {
+        for (key, value) in &[("cursor", "2 4"), ("mode", "visual"), ("register", "q dd")] {
+            let postcondition = parse(key, value).unwrap().unwrap();
+            let (key, value) = postcondition.to_setting();
+
+            assert_eq!(parse(&key, &value), Some(Ok(postcondition)));
+        }
+    }
+}
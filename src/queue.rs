@@ -0,0 +1,120 @@
+//! Global evaluation queue.
+//!
+//! Every evaluation spawns an nvim instance, so the number of evaluations running at the same
+//! time is bounded, and each user can only have one evaluation in flight.
+
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct EvaluationQueue {
+    workers: Semaphore,
+    waiting: AtomicUsize,
+    /// For each user, whether an evaluation is in flight and when the last one finished.
+    users: Mutex<HashMap<UserId, (bool, Instant)>>,
+}
+
+impl TypeMapKey for EvaluationQueue {
+    type Value = Arc<EvaluationQueue>;
+}
+
+/// Why an evaluation was not accepted in the queue.
+pub enum Refusal {
+    /// The user already has an evaluation queued or running.
+    Busy,
+    /// The user has to wait that long before submitting again.
+    Cooldown(Duration),
+}
+
+impl EvaluationQueue {
+    /// Number of nvim instances allowed to run at the same time.
+    pub const WORKERS: usize = 2;
+    /// Minimum delay between the end of an evaluation and the start of the next for a user.
+    pub const COOLDOWN: Duration = Duration::from_secs(5);
+
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: Semaphore::new(workers),
+            waiting: AtomicUsize::new(0),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves a place in the queue for `user`.
+    pub fn admit(queue: &Arc<Self>, user: UserId) -> Result<Ticket, Refusal> {
+        let mut users = queue.users.lock().unwrap();
+
+        if let Some((busy, last)) = users.get(&user) {
+            if *busy {
+                return Err(Refusal::Busy);
+            }
+
+            let elapsed = last.elapsed();
+            if elapsed < Self::COOLDOWN {
+                return Err(Refusal::Cooldown(Self::COOLDOWN - elapsed));
+            }
+        }
+
+        users.insert(user, (true, Instant::now()));
+
+        Ok(Ticket {
+            queue: Arc::clone(queue),
+            user,
+        })
+    }
+}
+
+/// A place in the queue, the user can submit again once it is dropped.
+pub struct Ticket {
+    queue: Arc<EvaluationQueue>,
+    user: UserId,
+}
+
+impl Ticket {
+    /// Number of evaluations that will run before this one, zero if it can run right away.
+    pub fn position(&self) -> usize {
+        if self.queue.workers.available_permits() > 0 {
+            0
+        } else {
+            self.queue.waiting.load(Ordering::SeqCst) + 1
+        }
+    }
+
+    /// Waits for a free worker, the semaphore hands them out in FIFO order.
+    pub async fn run(&self) -> SemaphorePermit<'_> {
+        let _waiting = Waiting::new(&self.queue.waiting);
+
+        self.queue.workers.acquire().await
+    }
+}
+
+/// Counts an evaluation as waiting until it is dropped, even when the command is cancelled
+/// while waiting.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::SeqCst);
+        Waiting(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        if let Ok(mut users) = self.queue.users.lock() {
+            users.insert(self.user, (false, Instant::now()));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Search of short solutions, to estimate how hard a challenge is.
+//!
+//! Sequences of commands from a fixed vocabulary are tried in a single nvim instance, keeping the
+//! ones whose output is the closest to the expected one. Candidates producing the expected output
+//! are then evaluated as any submission, so only valid solutions are reported.
+
+use nvim_rs::Value;
+
+use std::collections::HashSet;
+
+use crate::challenge::{Challenge, TextBlock};
+use crate::constraint::tokenize;
+use crate::engine::{create_nvim_instance, emulate, prepare, EngineError, Nvim, Prepared};
+
+/// The commands solutions are made of, written as in a mapping.
+///
+/// Commands depending on the previous candidates, like `.` or `u`, are left out since the
+/// instance is reused.
+pub const VOCABULARY: &[&str] = &[
+    "x",
+    "X",
+    "dd",
+    "D",
+    "J",
+    "gJ",
+    "p",
+    "P",
+    "~",
+    "dw",
+    "de",
+    "db",
+    "diw",
+    "daw",
+    "dap",
+    "dj",
+    "dk",
+    "dG",
+    "dgg",
+    "yy",
+    "yw",
+    "xp",
+    "ddp",
+    "guu",
+    "gUU",
+    "g~~",
+    ">>",
+    "<<",
+    "==",
+    "gqq",
+    "<C-a>",
+    "<C-x>",
+    "j",
+    "k",
+    "w",
+    "b",
+    "e",
+    "W",
+    "B",
+    "0",
+    "^",
+    "$",
+    "gg",
+    "G",
+    "%",
+    "}",
+    "{",
+    ":sort<CR>",
+    ":sort!<CR>",
+    ":sort u<CR>",
+    ":sort n<CR>",
+    ":g/^/m0<CR>",
+    ":g/^$/d<CR>",
+    ":%j<CR>",
+    ":%s/\\s\\+$//<CR>",
+];
+
+/// Bounds of the search.
+pub struct Limits {
+    /// How many commands a solution can have.
+    pub depth: usize,
+    /// How many sequences are extended at each step.
+    pub width: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            depth: 4,
+            width: 24,
+        }
+    }
+}
+
+pub struct Solution {
+    pub keys: String,
+    pub score: usize,
+}
+
+pub struct Search {
+    /// The shortest valid solution found.
+    pub solution: Option<Solution>,
+    /// How many sequences were tried.
+    pub explored: usize,
+}
+
+/// Registers cleared before each candidate, so that they don't leak from the previous one.
+const RESET: &str = "for _, name in ipairs({ '\"', '-', '0', '1', '2', '3', '4', '5', '6', '7', \
+    '8', '9' }) do vim.fn.setreg(name, '') end";
+
+/// How many lines have to be removed from `lines` and added to get `expected`.
+fn distance(lines: &[String], expected: &[String]) -> usize {
+    // Longest common subsequence of the lines
+    let mut previous = vec![0; expected.len() + 1];
+
+    for line in lines {
+        let mut current = vec![0; expected.len() + 1];
+
+        for (index, other) in expected.iter().enumerate() {
+            current[index + 1] = if line == other {
+                previous[index] + 1
+            } else {
+                std::cmp::max(previous[index + 1], current[index])
+            };
+        }
+
+        previous = current;
+    }
+
+    lines.len() + expected.len() - 2 * previous[expected.len()]
+}
+
+/// Searches the shortest solution of `chall` within `limits`.
+///
+/// Only the output of the input buffer guides the search, so challenges with several buffers are
+/// rarely solved.
+pub async fn solve(chall: &Challenge, limits: &Limits) -> Result<Search, EngineError> {
+    let nvim = create_nvim_instance().await?;
+    search(&nvim, chall, limits).await
+}
+
+async fn search(nvim: &Nvim, chall: &Challenge, limits: &Limits) -> Result<Search, EngineError> {
+    // The buffers are set up as for a submission, then only their lines are reset
+    let Prepared { buf, win, others } = prepare(nvim, chall).await?;
+    nvim.command("set undolevels=-1").await?;
+
+    let mut costs = Vec::new();
+    for word in VOCABULARY {
+        costs.push(nvim.replace_termcodes(word, true, true, true).await?.len());
+    }
+
+    let mut beam: Vec<(Vec<usize>, usize)> = vec![(Vec::new(), 0)];
+    let mut seen = HashSet::new();
+    let mut explored = 0;
+    let mut best: Option<Solution> = None;
+
+    for _ in 0..limits.depth {
+        let mut next = Vec::new();
+
+        for (words, score) in &beam {
+            for (index, cost) in costs.iter().enumerate() {
+                let score = score + cost;
+                if best.as_ref().is_some_and(|best| score >= best.score) {
+                    continue;
+                }
+
+                let mut candidate = words.clone();
+                candidate.push(index);
+                let keys: String = candidate.iter().map(|&word| VOCABULARY[word]).collect();
+
+                let tokens = tokenize(&keys);
+                if chall
+                    .constraints
+                    .iter()
+                    .any(|constraint| constraint.check_keys(&tokens).is_some())
+                {
+                    continue;
+                }
+
+                buf.set_lines(0, -1, false, chall.input.content.clone())
+                    .await?;
+                for (other, other_buf) in &others {
+                    other_buf
+                        .set_lines(0, -1, false, other.input.content.clone())
+                        .await?;
+                }
+                nvim.set_current_win(&win).await?;
+                win.set_buf(&buf).await?;
+                win.set_cursor((1, 0)).await?;
+                nvim.exec_lua(RESET, Vec::<Value>::new()).await?;
+
+                let parsed = nvim.replace_termcodes(&keys, true, true, true).await?;
+                nvim.feedkeys(&parsed, "ntx", true).await?;
+                explored += 1;
+
+                let lines = buf.get_lines(0, -1, false).await?;
+                let cursor = win.get_cursor().await?;
+                // Yanks only change the unnamed register, they lead to other states with a put
+                let mut register = Vec::new();
+                for function in ["getreg", "getregtype"] {
+                    let value = nvim
+                        .call_function(function, vec![Value::from("\"")])
+                        .await?;
+                    register.push(value.as_str().unwrap_or_default().to_owned());
+                }
+
+                if !seen.insert((lines.clone(), cursor, register)) {
+                    continue;
+                }
+
+                let output = TextBlock {
+                    content: lines,
+                    ..chall.output.clone()
+                };
+
+                if chall.output.matches(&output, &chall.comparison) {
+                    // Constraints, checkers and generated inputs are only checked by the engine
+                    let evaluation = emulate(chall, &keys).await?;
+
+                    if evaluation.failure.is_none()
+                        && best
+                            .as_ref()
+                            .is_none_or(|best| evaluation.score < best.score)
+                    {
+                        best = Some(Solution {
+                            keys,
+                            score: evaluation.score,
+                        });
+                    }
+                    continue;
+                }
+
+                next.push((
+                    distance(&output.content, &chall.output.content),
+                    score,
+                    candidate,
+                ));
+            }
+        }
+
+        next.sort_by_key(|(distance, score, _)| (*distance, *score));
+        next.truncate(limits.width);
+
+        beam = next
+            .into_iter()
+            .map(|(_, score, words)| (words, score))
+            .collect();
+    }
+
+    Ok(Search {
+        solution: best,
+        explored,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn counts_lines_to_remove_and_add() {
+        assert_eq!(distance(&lines("a b c"), &lines("a b c")), 0);
+        assert_eq!(distance(&lines(""), &lines("a b")), 2);
+        assert_eq!(distance(&lines("a b"), &lines("")), 2);
+        assert_eq!(distance(&lines("a x c"), &lines("a b c")), 2);
+        assert_eq!(distance(&lines("a b c"), &lines("a c")), 1);
+        // Only one line can be kept when the order is reversed
+        assert_eq!(distance(&lines("a b"), &lines("b a")), 2);
+    }
+}
@@ -0,0 +1,187 @@
+//! Evaluation of key sequences in classic Vim, driven by a script in silent Ex mode.
+//!
+//! Only the output of the input is compared, so challenges relying on nvim for anything else,
+//! like checkers or post-conditions, are only evaluated in nvim.
+
+use log::warn;
+
+use tokio::process::Command;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::challenge::{Challenge, FileFormat, TextBlock};
+use crate::constraint::Constraint;
+
+const VIMPATH: &str = "vim";
+
+/// How long the keys can run, since a script can't be interrupted as an embedded nvim.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Loads the input, feeds the keys as nvim does and writes the text of the input buffer, preceded
+/// by its `'fileformat'` and `'endofline'`.
+///
+/// Key notations are replaced one by one, since Vim has no `nvim_replace_termcodes`. Only the
+/// notations made of letters, digits and dashes, like `<S-Tab>`, or ending with a symbol of a
+/// control key, like `<C-@>`, are evaluated, so that the keys can't close the string.
+const SCRIPT: &str = r#"set nofixendofline
+edit ++ff=unix ++enc=utf-8 input
+let s:input = bufnr('%')
+call cursor(1, 1)
+let &l:fileformat = '{fileformat}'
+let &l:endofline = {eol}
+let s:keys = join(readfile('keys', 'b'), "\n")
+function! s:Key(notation) abort
+  let l:name = a:notation[1:-2]
+  if l:name !~# '^[-A-Za-z0-9]*[-A-Za-z0-9@[\]^_]$'
+    return a:notation
+  endif
+  return eval('"\<' . l:name . '>"')
+endfunction
+let s:keys = substitute(s:keys, '<[^<>]\+>', '\=s:Key(submatch(0))', 'g')
+call feedkeys(s:keys, 'ntx')
+call writefile([getbufvar(s:input, '&fileformat'), getbufvar(s:input, '&endofline')]
+    \ + getbufline(s:input, 1, '$'), 'output')
+qall!
+"#;
+
+/// Whether the keys of `chall` can be evaluated in Vim.
+pub fn supports(chall: &Challenge) -> bool {
+    chall.checker.is_none()
+        && chall.generator.is_none()
+        && chall.postconditions.is_empty()
+        && chall.buffers.is_empty()
+        && chall.file.is_none()
+        && chall.runtime.is_empty()
+        && !chall.constraints.iter().any(Constraint::is_dynamic)
+}
+
+/// Whether Vim can be started, checked once.
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+    *AVAILABLE.get_or_init(|| {
+        std::process::Command::new(VIMPATH)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+/// A directory of its own for each run, since submissions are evaluated concurrently.
+///
+/// Runs are kept in a temporary directory of the bot, that can be lost at any time. It is also
+/// the home of Vim, so that its own files are not read, but the keys can still reach other files
+/// by their absolute path.
+fn workspace() -> io::Result<PathBuf> {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = env::temp_dir().join("vim-golf-bot").join(format!(
+        "{}-{}",
+        process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+/// Feeds `keys` to the input of `chall` in Vim, returns the output.
+///
+/// `None` is returned when the keys quit Vim or don't end, and an error when Vim can't be run.
+pub async fn run(chall: &Challenge, keys: &str) -> io::Result<Option<TextBlock>> {
+    let dir = workspace()?;
+    let output = run_in(&dir, &chall.input, keys).await;
+    let _ = fs::remove_dir_all(&dir);
+
+    output
+}
+
+async fn run_in(dir: &Path, input: &TextBlock, keys: &str) -> io::Result<Option<TextBlock>> {
+    let mut text = input.content.join("\n");
+    text.push('\n');
+    fs::write(dir.join("input"), text)?;
+    fs::write(dir.join("keys"), keys)?;
+    fs::write(
+        dir.join("script.vim"),
+        SCRIPT
+            .replace("{fileformat}", input.fileformat.option())
+            .replace("{eol}", if input.eol { "1" } else { "0" }),
+    )?;
+
+    let child = Command::new(VIMPATH)
+        .args([
+            "-Z",
+            "-N",
+            "-u",
+            "NONE",
+            "-i",
+            "NONE",
+            "--noplugin",
+            "-n",
+            "-es",
+            "-S",
+            "script.vim",
+        ])
+        .current_dir(dir)
+        .env("HOME", dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status();
+
+    // Vim failing to start is an error, not keys failing to run
+    match tokio::time::timeout(TIMEOUT, child).await {
+        Ok(status) => {
+            status?;
+        }
+        Err(_) => {
+            warn!("Vim did not end with : {}", keys);
+            return Ok(None);
+        }
+    }
+
+    // Keys like `<M-x>` can insert bytes that are not UTF-8, the output is then wrong anyway
+    let written = match fs::read(dir.join("output")) {
+        Ok(written) => String::from_utf8_lossy(&written).into_owned(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    // Lines are split by hand, `str::lines` would also remove a trailing `\r`
+    let mut lines = written
+        .strip_suffix('\n')
+        .unwrap_or(&written)
+        .split('\n')
+        .map(str::to_owned);
+    let fileformat = lines.next();
+    let eol = lines.next();
+
+    Ok(Some(TextBlock {
+        lang: input.lang.clone(),
+        fileformat: fileformat
+            .as_deref()
+            .and_then(FileFormat::from_option)
+            .unwrap_or_default(),
+        eol: eol.as_deref() != Some("0"),
+        content: lines.collect(),
+    }))
+}
+
+/// Whether `keys` solve `chall` in Vim.
+pub async fn works(chall: &Challenge, keys: &str) -> io::Result<bool> {
+    Ok(match run(chall, keys).await? {
+        Some(output) => chall.output.matches(&output, &chall.comparison),
+        None => false,
+    })
+}